@@ -0,0 +1,192 @@
+# English texts of the game, the other languages fall back to these
+
+## Start screen
+
+game-title =
+    MOON
+    2023
+start-story =
+    The freighter ship was supposed to pick you up
+    several months ago, but it never came.
+    Your contact from the corporation went on sick leave
+    and never returned.
+    Then the communication channel closed.
+    In the faint radio broadcasts you capture with
+    your make-shift aerial you hear garbled fragments
+    of news ... virus .... millions dead
+
+    Luckily the expedition ship Aurora is returning
+    from their last multi-year Jovial mission.
+    They don't have enough fuel to pick you up,
+    but it is in your hands to harvest enough
+    helium-3 to change their minds.
+    This might be the last chance to get out of
+    this foresaken barren rock.
+
+    Start with building a harvester
+    and then work expeditiously
+    to gather 5 tanks of helium
+    before the expedition ship
+    passes the point of no return.
+    use WASD to drive around and
+    mouse to interact with the harvesters.
+    You have to choose beteen storing helium
+    and building more harvesters
+players-one =
+    Tab: one player
+    O: settings
+    T: tutorial
+players-many =
+    Tab: { $count } players
+    P2: { $keys }
+    O: settings
+    T: tutorial
+show-achievements = A: achievements
+show-high-scores = A: high scores
+high-scores-title = High scores
+high-scores-empty = No runs yet
+achievements-title = Achievements { $unlocked }/{ $total }
+
+## Tutorial
+
+tutorial-drive = Drive to the harvester with { $keys }
+tutorial-collect =
+    Once it has some helium, click the harvester
+    or press { $interact } next to it to collect
+tutorial-unload =
+    Drive back to the base and click it
+    or press { $interact } next to it to unload
+tutorial-build =
+    Press Space to open the panel, then press B and click
+    the map to build a harvester for { $build } helium
+tutorial-craft =
+    Fill the storage with { $craft } helium
+    and click the tank button to craft a canister
+tutorial-done = Tutorial done, the ship is on its way
+
+## Achievements
+
+achievement-unlocked = Achievement unlocked: { $title }
+achievement-flawless = Flawless
+achievement-flawless-description = Win with no harvester breakdowns
+achievement-minimalist = Minimalist
+achievement-minimalist-description = Win with only three harvesters
+achievement-last-second = Last second
+achievement-last-second-description = Deliver helium with <10s left
+achievement-no-waste = No waste
+achievement-no-waste-description = Win with no harvester ever full
+
+## Terrain and panel
+
+open-panel = Open info panel
+back-to-vehicle = Back to vehicle
+base-helium =
+    Helium:
+    { $helium }/{ $max }
+buggy-helium = Helium amount: { $helium }
+buggy-player-helium =
+    Player { $player }
+    Helium amount: { $helium }
+harvester-title = Harvester { $slot }
+harvester-working = Status: Working
+harvester-full = Status: Full
+harvester-broken = Status: Broken
+harvester-helium = Helium amount: { $helium }/{ $max }
+harvester-collect = Click to collect helium
+harvester-repair = Click to repair
+harvester-collecting = Collecting...
+harvester-waiting = Waiting...
+ship-arrival =
+    { $secs ->
+        [one] { $secs } second left before arrival
+       *[other] { $secs } seconds left before arrival
+    }
+
+## Event log
+
+event-harvester-broke = Harvester { $slot } broke
+event-harvester-full = Harvester { $slot } full
+event-harvester-fixed = Harvester { $slot } fixed
+event-harvester-built = Harvester { $slot } built
+event-storage-full = Storage full
+event-canister-crafted = Canister { $index } crafted
+event-alert = { $message } at { $time }
+
+## Finish screen
+
+ending-win =
+    With great effort you finish the harvest in time. You hail the Aurora and the crew is happy to get you on board.
+    They didn't hear any news from Earth for a while now and you're the only one who can give them at least some scraps of the information.
+    Together you fly home and hope that your loved ones are alive and well.
+ending-lose =
+    You see the ship fly by
+    over your head.
+    There is no sound in space, only silent dread.
+    You'll stay here mining helium
+    hoping that some day someone
+    will be able to rescue you.
+run-summary =
+    Score: { $score }
+    Helium harvested: { $harvested }
+    Helium delivered: { $delivered }
+    Repairs: { $repairs }/{ $breakdowns }
+    Broken: { $broken }s Full: { $full }s
+    Driven: { $meters }m
+    Harvesters built: { $built }
+    Last canister: { $last }
+export-hint = E: export stats
+export-saved = Saved to { $path }
+export-failed = Can't save stats
+export-printed = Printed to the console
+high-score-new = New high score!
+high-score-name = Name:{ " " }
+high-score-save = Enter: save
+high-score-saved = Saved:{ " " }
+play-again = Space: play again
+
+## Menus
+
+sound-on = Sound on
+sound-off = Sound off
+net-host-paused = The host paused the game
+net-host-resumed = The host resumed the game
+pause-title = Paused
+pause-hint = Up/Down: choose  Enter: select  Esc: resume
+pause-resume = Resume
+pause-settings = Settings
+pause-restart = Restart
+pause-quit = Quit to title
+settings-title = Settings
+settings-hint = Up/Down: choose  Left/Right: change  Enter: set key  Esc: back
+settings-back = Back
+page-display = Display
+page-audio = Audio
+page-controls = Controls
+page-gameplay = Gameplay
+setting-window = Window
+window-windowed = windowed
+window-borderless = borderless
+window-fullscreen = fullscreen
+setting-window-size = Window size
+setting-master-volume = Master volume
+setting-music-volume = Music volume
+setting-sound-volume = Sound volume
+setting-mute = Mute ({ $key })
+setting-on = [on]
+setting-off = [off]
+setting-key = P{ $player } { $action }
+setting-press-key = [press a key]
+action-forward = Forward
+action-brake = Brake
+action-left = Left
+action-right = Right
+action-interact = Interact
+setting-language = Language
+setting-colors = Colors
+palette-standard = standard
+palette-deuteranopia = deuteranopia
+palette-protanopia = protanopia
+palette-tritanopia = tritanopia
+setting-tooltip-delay = Tooltip delay
+tooltip-delay-off = off
+tooltip-delay-secs = { $secs }s
@@ -0,0 +1,196 @@
+# Русские тексты игры, недостающие берутся из en.ftl
+
+## Начальный экран
+
+game-title =
+    ЛУНА
+    2023
+start-story =
+    Грузовой корабль должен был забрать вас
+    ещё несколько месяцев назад, но так и не прилетел.
+    Ваш контакт в корпорации ушёл на больничный
+    и не вернулся.
+    Потом закрылся канал связи.
+    В слабых радиопередачах, которые ловит
+    самодельная антенна, слышны обрывки
+    новостей ... вирус .... миллионы погибших
+
+    К счастью, экспедиционный корабль «Аврора»
+    возвращается из многолетней миссии к Юпитеру.
+    Топлива, чтобы забрать вас, у них не хватит,
+    но в ваших силах добыть столько гелия-3,
+    чтобы они передумали.
+    Возможно, это последний шанс выбраться
+    с этого забытого богом камня.
+
+    Начните с постройки харвестера,
+    а затем работайте без промедления,
+    чтобы собрать 5 баков гелия,
+    пока корабль экспедиции не прошёл
+    точку невозврата.
+    WASD - ехать, мышь - работать с харвестерами.
+    Придётся выбирать между запасом гелия
+    и постройкой новых харвестеров
+players-one =
+    Tab: один игрок
+    O: настройки
+    T: обучение
+players-many =
+    Tab: { $count ->
+        [one] { $count } игрок
+        [few] { $count } игрока
+       *[other] { $count } игроков
+    }
+    P2: { $keys }
+    O: настройки
+    T: обучение
+show-achievements = A: достижения
+show-high-scores = A: рекорды
+high-scores-title = Рекорды
+high-scores-empty = Забегов пока нет
+achievements-title = Достижения { $unlocked }/{ $total }
+
+## Обучение
+
+tutorial-drive = Доберитесь до харвестера: { $keys }
+tutorial-collect =
+    Когда в нём появится гелий, нажмите на харвестер
+    или { $interact } рядом с ним, чтобы забрать его
+tutorial-unload =
+    Вернитесь на базу и нажмите на неё
+    или { $interact } рядом с ней, чтобы выгрузить гелий
+tutorial-build =
+    Нажмите пробел, чтобы открыть панель, затем B и место
+    на карте, чтобы построить харвестер за { $build } гелия
+tutorial-craft =
+    Наполните хранилище до { $craft } гелия
+    и нажмите на кнопку баллона, чтобы его собрать
+tutorial-done = Обучение пройдено, корабль уже летит
+
+## Достижения
+
+achievement-unlocked = Достижение получено: { $title }
+achievement-flawless = Без поломок
+achievement-flawless-description = Победа без единой поломки
+achievement-minimalist = Минималист
+achievement-minimalist-description = Победа всего с тремя харвестерами
+achievement-last-second = В последний миг
+achievement-last-second-description = Сдать гелий за <10 с до конца
+achievement-no-waste = Без потерь
+achievement-no-waste-description = Победа, ни один харвестер не переполнен
+
+## Поверхность и панель
+
+open-panel = Открыть панель
+back-to-vehicle = Назад к вездеходу
+base-helium =
+    Гелий:
+    { $helium }/{ $max }
+buggy-helium = Гелия: { $helium }
+buggy-player-helium =
+    Игрок { $player }
+    Гелия: { $helium }
+harvester-title = Харвестер { $slot }
+harvester-working = Состояние: работает
+harvester-full = Состояние: заполнен
+harvester-broken = Состояние: сломан
+harvester-helium = Гелия: { $helium }/{ $max }
+harvester-collect = Нажмите, чтобы забрать гелий
+harvester-repair = Нажмите, чтобы починить
+harvester-collecting = Добыча...
+harvester-waiting = Ожидание...
+ship-arrival =
+    { $secs ->
+        [one] До прилёта { $secs } секунда
+        [few] До прилёта { $secs } секунды
+       *[other] До прилёта { $secs } секунд
+    }
+
+## Журнал событий
+
+event-harvester-broke = Харвестер { $slot } сломан
+event-harvester-full = Харвестер { $slot } полон
+event-harvester-fixed = Харвестер { $slot } починен
+event-harvester-built = Харвестер { $slot } построен
+event-storage-full = Хранилище полно
+event-canister-crafted = Бак { $index } готов
+event-alert = { $message } в { $time }
+
+## Финальный экран
+
+ending-win =
+    Ценой больших усилий вы успеваете собрать урожай вовремя. Вы вызываете «Аврору», и экипаж рад взять вас на борт.
+    Они давно не слышали новостей с Земли, и только вы можете рассказать им хоть что-то.
+    Вместе вы летите домой и надеетесь, что ваши близкие живы и здоровы.
+ending-lose =
+    Корабль пролетает
+    над вашей головой.
+    В космосе нет звука, только безмолвный ужас.
+    Вы остаётесь добывать гелий
+    в надежде, что однажды кто-нибудь
+    сможет вас спасти.
+run-summary =
+    Очки: { $score }
+    Гелия добыто: { $harvested }
+    Гелия сдано: { $delivered }
+    Ремонты: { $repairs }/{ $breakdowns }
+    Сломаны: { $broken }с Полны: { $full }с
+    Пройдено: { $meters }м
+    Построено харвестеров: { $built }
+    Последний бак: { $last }
+export-hint = E: сохранить статистику
+export-saved = Сохранено в { $path }
+export-failed = Не удалось сохранить
+export-printed = Выведено в консоль
+high-score-new = Новый рекорд!
+high-score-name = Имя:{ " " }
+high-score-save = Enter: сохранить
+high-score-saved = Сохранено:{ " " }
+play-again = Пробел: играть снова
+
+## Меню
+
+sound-on = Звук включён
+sound-off = Звук выключен
+net-host-paused = Хост поставил игру на паузу
+net-host-resumed = Хост продолжил игру
+pause-title = Пауза
+pause-hint = Вверх/вниз: выбор  Enter: выбрать  Esc: продолжить
+pause-resume = Продолжить
+pause-settings = Настройки
+pause-restart = Заново
+pause-quit = В главное меню
+settings-title = Настройки
+settings-hint = Вверх/вниз: выбор  Влево/вправо: изменить  Enter: задать клавишу  Esc: назад
+settings-back = Назад
+page-display = Экран
+page-audio = Звук
+page-controls = Управление
+page-gameplay = Игра
+setting-window = Окно
+window-windowed = в окне
+window-borderless = без рамки
+window-fullscreen = полный экран
+setting-window-size = Размер окна
+setting-master-volume = Общая громкость
+setting-music-volume = Музыка
+setting-sound-volume = Эффекты
+setting-mute = Без звука ({ $key })
+setting-on = [вкл]
+setting-off = [выкл]
+setting-key = И{ $player } { $action }
+setting-press-key = [нажмите клавишу]
+action-forward = Вперёд
+action-brake = Тормоз
+action-left = Влево
+action-right = Вправо
+action-interact = Действие
+setting-language = Язык
+setting-colors = Цвета
+palette-standard = обычные
+palette-deuteranopia = дейтеранопия
+palette-protanopia = протанопия
+palette-tritanopia = тританопия
+setting-tooltip-delay = Задержка подсказок
+tooltip-delay-off = нет
+tooltip-delay-secs = { $secs } с
@@ -0,0 +1,136 @@
+//! Packs every layer of every `.aseprite` file in `assets` into one texture atlas,
+//! so the game neither parses the files nor binds a texture per sprite when it runs.
+//!
+//! Writes `atlas.png` and `atlas_index.rs`, the names and places of the regions
+//! and the slices of the files, to `OUT_DIR`.
+//! Regions are named like the layers: `spritepanel8.aseprite#harv3red`, frames after the first
+//! one get their number appended: `iconcenter3.aseprite#red@2`.
+
+use std::{fmt::Write, fs, path::Path};
+
+use asefile::{AnimationDirection, AsepriteFile};
+use image::RgbaImage;
+
+const ASSETS: &str = "assets";
+/// Wide enough for the biggest pictures side by side, the height grows with the content
+const ATLAS_WIDTH: u32 = 2048;
+/// Empty pixels around every region, so neighbours don't bleed in when the picture is scaled
+const PADDING: u32 = 1;
+
+fn main() {
+    println!("cargo:rerun-if-changed={ASSETS}");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR set by cargo");
+
+    let mut paths: Vec<_> = fs::read_dir(ASSETS)
+        .expect("assets directory")
+        .map(|entry| entry.expect("readable assets directory").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "aseprite"))
+        .collect();
+    paths.sort();
+
+    let mut regions: Vec<(String, RgbaImage)> = vec![];
+    let mut files = String::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let file = AsepriteFile::read_file(&path)
+            .unwrap_or_else(|e| panic!("can't read {}: {e}", path.display()));
+
+        let durations: Vec<String> = (0..file.num_frames())
+            .map(|frame| format!("{:?}", file.frame(frame).duration() as f32 / 1000.0))
+            .collect();
+        let tags: Vec<String> = (0..file.num_tags())
+            .map(|tag| {
+                let tag = file.tag(tag);
+                let direction = match tag.animation_direction() {
+                    AnimationDirection::Forward => "Forward",
+                    AnimationDirection::Reverse => "Reverse",
+                    AnimationDirection::PingPong => "PingPong",
+                };
+                format!(
+                    "TagEntry {{ name: {:?}, from: {}, to: {}, direction: LoopDirection::{direction} }}",
+                    tag.name(),
+                    tag.from_frame(),
+                    tag.to_frame()
+                )
+            })
+            .collect();
+        let slices: Vec<String> = file
+            .slices()
+            .iter()
+            .filter_map(|slice| {
+                // Only the first key, the layout doesn't move between frames
+                let key = slice.keys.first()?;
+                let ((x, y), (width, height)) = (key.origin, key.size);
+                Some(format!("({:?}, [{x}, {y}, {width}, {height}])", slice.name))
+            })
+            .collect();
+        let mut layers = vec![];
+        for layer in file.layers() {
+            let mut frames = vec![];
+            for frame in 0..file.num_frames() {
+                let label = match frame {
+                    0 => layer.name().to_string(),
+                    frame => format!("{}@{frame}", layer.name()),
+                };
+                frames.push(regions.len().to_string());
+                let image = layer.frame(frame).image();
+                let image = RgbaImage::from_raw(image.width(), image.height(), image.into_raw())
+                    .expect("size of containers to match");
+                regions.push((format!("{name}#{label}"), image));
+            }
+            layers.push(format!("({:?}, &[{}])", layer.name(), frames.join(", ")));
+        }
+        writeln!(
+            files,
+            "    FileEntry {{ name: {name:?}, size: [{}, {}], durations: &[{}], tags: &[{}], slices: &[{}], layers: &[{}] }},",
+            file.width(),
+            file.height(),
+            durations.join(", "),
+            tags.join(", "),
+            slices.join(", "),
+            layers.join(", ")
+        )
+        .unwrap();
+    }
+
+    // Shelves of regions sorted by height, a new shelf starts when a row is full
+    let mut order: Vec<usize> = (0..regions.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((regions[i].1.height(), regions[i].1.width())));
+    let mut places = vec![[0; 4]; regions.len()];
+    let (mut x, mut y, mut shelf_height) = (PADDING, PADDING, 0);
+    for i in order {
+        let (width, height) = regions[i].1.dimensions();
+        assert!(
+            width + 2 * PADDING <= ATLAS_WIDTH,
+            "{} is too wide",
+            regions[i].0
+        );
+        if x + width + PADDING > ATLAS_WIDTH {
+            x = PADDING;
+            y += shelf_height + PADDING;
+            shelf_height = 0;
+        }
+        places[i] = [x, y, width, height];
+        x += width + PADDING;
+        shelf_height = shelf_height.max(height);
+    }
+    let atlas_height = y + shelf_height + PADDING;
+
+    let mut atlas = RgbaImage::new(ATLAS_WIDTH, atlas_height);
+    let mut index = String::new();
+    for ((name, image), [x, y, width, height]) in regions.iter().zip(&places) {
+        image::imageops::replace(&mut atlas, image, *x as i64, *y as i64);
+        writeln!(index, "    ({name:?}, [{x}, {y}, {width}, {height}]),").unwrap();
+    }
+    atlas
+        .save(Path::new(&out_dir).join("atlas.png"))
+        .expect("atlas written");
+
+    let source = format!(
+        "// Generated by build.rs from the files in `{ASSETS}`\n\n\
+         const ATLAS_SIZE: [u32; 2] = [{ATLAS_WIDTH}, {atlas_height}];\n\n\
+         const REGIONS: &[(&str, [u32; 4])] = &[\n{index}];\n\n\
+         const FILES: &[FileEntry] = &[\n{files}];\n"
+    );
+    fs::write(Path::new(&out_dir).join("atlas_index.rs"), source).expect("index written");
+}
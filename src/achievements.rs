@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::GameEvent,
+    i18n::Localization,
+    persist,
+    start::EndTimer,
+    stats::{count_events, in_run, RunStats},
+    terrain::CANISTERS_TO_WIN,
+    toast::Toast,
+    tutorial::TutorialRun,
+};
+
+use super::*;
+
+const SAVE_KEY: &str = "achievements";
+/// Deliveries this close to the end of the run count as last second
+const LAST_SECOND_DELIVERY_SECS: f32 = 10.0;
+/// Most harvesters a run may build and still earn `Achievement::Minimalist`
+const MINIMALIST_HARVESTERS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    /// Win without any harvester ever breaking
+    Flawless,
+    /// Win with three harvesters or fewer
+    Minimalist,
+    /// Deliver helium with under ten seconds left
+    LastSecond,
+    /// Win without ever letting a harvester sit full
+    NoWaste,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] = [
+        Achievement::Flawless,
+        Achievement::Minimalist,
+        Achievement::LastSecond,
+        Achievement::NoWaste,
+    ];
+
+    /// Messages are `achievement-<id>` and `achievement-<id>-description`
+    fn id(self) -> &'static str {
+        match self {
+            Achievement::Flawless => "flawless",
+            Achievement::Minimalist => "minimalist",
+            Achievement::LastSecond => "last-second",
+            Achievement::NoWaste => "no-waste",
+        }
+    }
+
+    pub fn title(self, localization: &Localization) -> String {
+        localization.get(&format!("achievement-{}", self.id()))
+    }
+
+    pub fn description(self, localization: &Localization) -> String {
+        localization.get(&format!("achievement-{}-description", self.id()))
+    }
+}
+
+/// Unlocked on this machine, kept between runs
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Achievements(pub Vec<Achievement>);
+
+impl Achievements {
+    fn unlock(
+        &mut self,
+        achievement: Achievement,
+        toasts: &mut EventWriter<Toast>,
+        localization: &Localization,
+    ) {
+        if self.0.contains(&achievement) {
+            return;
+        }
+        self.0.push(achievement);
+        persist::save(SAVE_KEY, &*self);
+        toasts.send(Toast(localization.format(
+            "achievement-unlocked",
+            &[("title", achievement.title(localization).into())],
+        )));
+    }
+
+    pub fn list(&self, localization: &Localization) -> String {
+        let mut list = localization.format(
+            "achievements-title",
+            &[
+                ("unlocked", self.0.len().into()),
+                ("total", Achievement::ALL.len().into()),
+            ],
+        );
+        list.push('\n');
+        for achievement in Achievement::ALL {
+            let mark = if self.0.contains(&achievement) {
+                'x'
+            } else {
+                ' '
+            };
+            list += &format!(
+                "\n[{mark}] {}\n    {}",
+                achievement.title(localization),
+                achievement.description(localization)
+            );
+        }
+        list
+    }
+}
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(persist::load::<Achievements>(SAVE_KEY))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_run)
+                    .with_system(check_achievements.after(count_events)),
+            );
+    }
+}
+
+/// Runs after the stats took in this frame's events, so they are up to date when the run is won
+fn check_achievements(
+    mut events: EventReader<GameEvent>,
+    stats: Res<RunStats>,
+    timer: Query<&EndTimer>,
+    mut achievements: ResMut<Achievements>,
+    mut toasts: EventWriter<Toast>,
+    localization: Res<Localization>,
+    tutorial_run: Option<Res<TutorialRun>>,
+) {
+    if tutorial_run.is_some() {
+        return;
+    }
+    for event in events.iter() {
+        match *event {
+            GameEvent::HeliumDelivered { .. } => {
+                let time_left = timer
+                    .get_single()
+                    .map_or(f32::MAX, |t| t.timer.remaining_secs());
+                if time_left < LAST_SECOND_DELIVERY_SECS {
+                    achievements.unlock(Achievement::LastSecond, &mut toasts, &localization);
+                }
+            }
+            GameEvent::CanisterCrafted { index } if index + 1 >= CANISTERS_TO_WIN => {
+                if stats.breakdowns == 0 {
+                    achievements.unlock(Achievement::Flawless, &mut toasts, &localization);
+                }
+                if stats.harvesters_built <= MINIMALIST_HARVESTERS {
+                    achievements.unlock(Achievement::Minimalist, &mut toasts, &localization);
+                }
+                if stats.harvesters.iter().all(|h| h.times_full == 0) {
+                    achievements.unlock(Achievement::NoWaste, &mut toasts, &localization);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! `.aseprite` files watched through the `AssetServer` with the `hot_reload` feature,
+//! every save paints the new pixels and timing over the atlas `build.rs` packed.
+//!
+//! Only layers that were there when the game was built can change,
+//! a new layer or a resized canvas needs a rebuild.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+
+use crate::atlas::{Atlas, LoopDirection, Tag};
+
+use super::*;
+
+#[derive(TypeUuid)]
+#[uuid = "5b8a3e0e-8f4c-4b8e-9b7e-2f0f6b7c9d41"]
+pub struct Aseprite {
+    /// RGBA pixels of every frame by layer name
+    layers: HashMap<String, Vec<(UVec2, Vec<u8>)>>,
+    /// Seconds every frame stays on screen
+    durations: Vec<f32>,
+    tags: HashMap<String, Tag>,
+}
+
+#[derive(Default)]
+pub struct AsepriteLoader;
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file = asefile::AsepriteFile::read(bytes)?;
+            let layers = file
+                .layers()
+                .map(|layer| {
+                    let frames = (0..file.num_frames())
+                        .map(|frame| {
+                            let image = layer.frame(frame).image();
+                            (UVec2::new(image.width(), image.height()), image.into_raw())
+                        })
+                        .collect();
+                    (layer.name().to_string(), frames)
+                })
+                .collect();
+            let durations = (0..file.num_frames())
+                .map(|frame| file.frame(frame).duration() as f32 / 1000.0)
+                .collect();
+            let tags = (0..file.num_tags())
+                .map(|tag| {
+                    let tag = file.tag(tag);
+                    let frames = tag.from_frame() as usize..=tag.to_frame() as usize;
+                    let direction = match tag.animation_direction() {
+                        asefile::AnimationDirection::Forward => LoopDirection::Forward,
+                        asefile::AnimationDirection::Reverse => LoopDirection::Reverse,
+                        asefile::AnimationDirection::PingPong => LoopDirection::PingPong,
+                    };
+                    (tag.name().to_string(), Tag { frames, direction })
+                })
+                .collect();
+            load_context.set_default_asset(LoadedAsset::new(Aseprite {
+                layers,
+                durations,
+                tags,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite", "ase"]
+    }
+}
+
+/// Keeps the watched files loaded, the handles are only held
+#[allow(dead_code)]
+#[derive(Resource)]
+struct Watched(Vec<Handle<Aseprite>>);
+
+pub struct AsepritePlugin;
+
+impl Plugin for AsepritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Aseprite>()
+            .init_asset_loader::<AsepriteLoader>()
+            .add_startup_system(watch)
+            .add_system(repaint);
+    }
+}
+
+fn watch(mut commands: Commands, asset_server: Res<AssetServer>, atlas: Res<Atlas>) {
+    let files = atlas
+        .files
+        .keys()
+        .map(|file| asset_server.load(*file))
+        .collect();
+    commands.insert_resource(Watched(files));
+}
+
+fn repaint(
+    mut events: EventReader<AssetEvent<Aseprite>>,
+    asset_server: Res<AssetServer>,
+    files: Res<Assets<Aseprite>>,
+    mut atlas: ResMut<Atlas>,
+    layouts: Res<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for event in events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {continue};
+        let (Some(path), Some(file)) = (asset_server.get_handle_path(handle), files.get(handle))
+        else {continue};
+        let name = path.path().to_string_lossy().to_string();
+        let Some(layout) = layouts.get(&atlas.layout) else {return};
+        let Some(texture) = images.get_mut(&layout.texture) else {return};
+        let Some(frames) = atlas.files.get_mut(name.as_str()) else {continue};
+
+        for (layer, pixels) in &file.layers {
+            let Some(regions) = frames.layer(layer) else {
+                warn!("{name}#{layer} is new, rebuild the game to see it");
+                continue;
+            };
+            for (region, (size, pixels)) in regions.iter().zip(pixels) {
+                let rect = layout.textures[*region];
+                if rect.size() != size.as_vec2() {
+                    warn!("{name}#{layer} changed size, rebuild the game to see it");
+                    continue;
+                }
+                let atlas_width = layout.size.x as usize;
+                let (x, y) = (rect.min.x as usize, rect.min.y as usize);
+                let row = size.x as usize * 4;
+                for line in 0..size.y as usize {
+                    let start = ((y + line) * atlas_width + x) * 4;
+                    texture.data[start..start + row]
+                        .copy_from_slice(&pixels[line * row..(line + 1) * row]);
+                }
+            }
+        }
+        frames.durations = file.durations.clone();
+        frames.tags = file.tags.clone();
+        info!("repainted {name}");
+    }
+}
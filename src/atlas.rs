@@ -0,0 +1,407 @@
+//! Every layer of the `.aseprite` files, packed into one texture by `build.rs`.
+//!
+//! Regions are named like `spritepanel8.aseprite#harv3red`, later frames get their number
+//! appended: `iconcenter3.aseprite#red@2`. Layers keep their whole canvas, so sprites made
+//! of several layers line up the same way they do in Aseprite.
+//!
+//! Slices mark places in a file, like the buttons of the panel: `spritepanel8.aseprite#exit`.
+
+use bevy::{
+    math::Rect,
+    render::{
+        render_resource::{FilterMode, SamplerDescriptor},
+        texture::{CompressedImageFormats, ImageSampler, ImageType},
+    },
+    utils::{HashMap, HashSet},
+};
+
+use super::*;
+
+struct FileEntry {
+    name: &'static str,
+    size: [u32; 2],
+    durations: &'static [f32],
+    tags: &'static [TagEntry],
+    /// Position and size of every slice by name
+    slices: &'static [(&'static str, [i32; 4])],
+    /// Region of every frame by layer name
+    layers: &'static [(&'static str, &'static [usize])],
+}
+
+struct TagEntry {
+    name: &'static str,
+    from: usize,
+    to: usize,
+    direction: LoopDirection,
+}
+
+/// Only built from the tags in the art
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum LoopDirection {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+include!(concat!(env!("OUT_DIR"), "/atlas_index.rs"));
+
+/// Part of the atlas with the size it's drawn at
+#[derive(Clone)]
+pub struct Region {
+    pub atlas: Handle<TextureAtlas>,
+    pub index: usize,
+    pub size: Vec2,
+}
+
+impl Region {
+    pub fn sprite(&self) -> TextureAtlasSprite {
+        TextureAtlasSprite {
+            index: self.index,
+            custom_size: Some(self.size),
+            ..default()
+        }
+    }
+
+    pub fn sprite_sheet(&self) -> SpriteSheetBundle {
+        SpriteSheetBundle {
+            sprite: self.sprite(),
+            texture_atlas: self.atlas.clone(),
+            ..default()
+        }
+    }
+}
+
+/// A rectangle marked in a file, the art can move it without touching the code
+#[derive(Clone, Copy, Default)]
+pub struct Slice {
+    /// Top left corner in the pixels of the file, y goes down like in Aseprite
+    min: Vec2,
+    size: Vec2,
+    canvas: Vec2,
+}
+
+impl Slice {
+    /// Size in world units
+    pub fn size(&self) -> Vec2 {
+        self.size * PIXEL_MULTIPLIER
+    }
+
+    /// Center in world units from the center of the picture, y goes up like in the game
+    pub fn center(&self) -> Vec2 {
+        let center = self.min + self.size / 2.0;
+        Vec2::new(
+            center.x - self.canvas.x / 2.0,
+            self.canvas.y / 2.0 - center.y,
+        ) * PIXEL_MULTIPLIER
+    }
+
+    /// Bottom left corner in world units from the center of the picture
+    pub fn bottom_left(&self) -> Vec2 {
+        self.center() - self.size() / 2.0
+    }
+}
+
+#[derive(Clone)]
+pub struct Tag {
+    pub frames: std::ops::RangeInclusive<usize>,
+    pub direction: LoopDirection,
+}
+
+/// Timing of the frames of a file, it can change while the game runs with `hot_reload`
+pub struct FileFrames {
+    layers: HashMap<String, Vec<usize>>,
+    pub durations: Vec<f32>,
+    pub tags: HashMap<String, Tag>,
+}
+
+impl FileFrames {
+    /// Regions of a layer's frames, `None` for layers added after the game was built
+    pub fn layer(&self, layer: &str) -> Option<&[usize]> {
+        self.layers.get(layer).map(|frames| &frames[..])
+    }
+
+    /// Frames of a tag in the order they play, the whole file if it has no such tag
+    fn frames(&self, layer: &str, tag: Option<&str>) -> Vec<(usize, f32)> {
+        let Some(regions) = self.layers.get(layer) else {
+            return vec![];
+        };
+        let mut order: Vec<usize> = (0..regions.len()).collect();
+        if let Some(tag) = tag.and_then(|tag| self.tags.get(tag)) {
+            order = tag.frames.clone().collect();
+            match tag.direction {
+                LoopDirection::Forward => {}
+                LoopDirection::Reverse => order.reverse(),
+                // The ends aren't doubled when it turns around
+                LoopDirection::PingPong => {
+                    let back: Vec<usize> = order.iter().rev().skip(1).copied().collect();
+                    order.extend(back.iter().take(back.len().saturating_sub(1)));
+                }
+            }
+        }
+        order
+            .into_iter()
+            .filter_map(|frame| Some((*regions.get(frame)?, *self.durations.get(frame)?)))
+            .collect()
+    }
+}
+
+#[derive(Resource)]
+pub struct Atlas {
+    pub layout: Handle<TextureAtlas>,
+    regions: HashMap<&'static str, usize>,
+    slices: HashMap<String, Slice>,
+    pub files: HashMap<&'static str, FileFrames>,
+}
+
+/// Where the sprites are looked up by name, the game draws them from the atlas
+/// and `--check-assets` writes down what it asked for
+pub trait Art {
+    fn region(&self, name: &str) -> Region;
+    fn slice(&self, name: &str) -> Slice;
+    fn animation(
+        &self,
+        file: &'static str,
+        layers: &[&'static str],
+        tag: Option<&'static str>,
+    ) -> Animation;
+}
+
+impl Atlas {
+    /// Everything `build.rs` wrote down, the regions pointing into `layout`
+    pub fn from_index(layout: Handle<TextureAtlas>) -> Self {
+        let files = FILES
+            .iter()
+            .map(|file| {
+                let frames = FileFrames {
+                    layers: file
+                        .layers
+                        .iter()
+                        .map(|(layer, regions)| (layer.to_string(), regions.to_vec()))
+                        .collect(),
+                    durations: file.durations.to_vec(),
+                    tags: file
+                        .tags
+                        .iter()
+                        .map(|tag| {
+                            let frames = tag.from..=tag.to;
+                            let direction = tag.direction;
+                            (tag.name.to_string(), Tag { frames, direction })
+                        })
+                        .collect(),
+                };
+                (file.name, frames)
+            })
+            .collect();
+        let slices = FILES
+            .iter()
+            .flat_map(|file| {
+                let [width, height] = file.size;
+                let canvas = Vec2::new(width as f32, height as f32);
+                file.slices
+                    .iter()
+                    .map(move |(name, [x, y, width, height])| {
+                        let slice = Slice {
+                            min: Vec2::new(*x as f32, *y as f32),
+                            size: Vec2::new(*width as f32, *height as f32),
+                            canvas,
+                        };
+                        (format!("{}#{name}", file.name), slice)
+                    })
+            })
+            .collect();
+
+        Atlas {
+            layout,
+            regions: REGIONS
+                .iter()
+                .enumerate()
+                .map(|(index, (name, _))| (*name, index))
+                .collect(),
+            slices,
+            files,
+        }
+    }
+
+    pub fn find_region(&self, name: &str) -> Option<Region> {
+        let index = *self.regions.get(name)?;
+        let [_, _, width, height] = REGIONS[index].1;
+        Some(Region {
+            atlas: self.layout.clone(),
+            index,
+            size: Vec2::new(width as f32, height as f32) * PIXEL_MULTIPLIER,
+        })
+    }
+
+    pub fn find_slice(&self, name: &str) -> Option<Slice> {
+        self.slices.get(name).copied()
+    }
+}
+
+impl Art for Atlas {
+    /// The region of a layer by its full name, a missing one is a bug in the code or the art
+    fn region(&self, name: &str) -> Region {
+        let Some(region) = self.find_region(name) else {
+            panic!("no {name} in the atlas, `--check-assets` lists what's missing");
+        };
+        region
+    }
+
+    /// A slice by its full name, like the regions a missing one is a bug
+    fn slice(&self, name: &str) -> Slice {
+        let Some(slice) = self.find_slice(name) else {
+            panic!("no slice {name} in the art, `--check-assets` lists what's missing");
+        };
+        slice
+    }
+
+    /// Frames are looked up as they play, so a missing layer or tag only shows in the check
+    fn animation(
+        &self,
+        file: &'static str,
+        layers: &[&'static str],
+        tag: Option<&'static str>,
+    ) -> Animation {
+        let animation = Animation::new(file, layers);
+        match tag {
+            Some(tag) => animation.tag(tag),
+            None => animation,
+        }
+    }
+}
+
+pub struct AtlasPlugin;
+
+impl Plugin for AtlasPlugin {
+    /// The atlas is part of the binary, so it's there before the first system runs
+    fn build(&self, app: &mut App) {
+        let mut image = Image::from_buffer(
+            include_bytes!(concat!(env!("OUT_DIR"), "/atlas.png")),
+            ImageType::Extension("png"),
+            CompressedImageFormats::NONE,
+            true,
+        )
+        .expect("valid atlas");
+        // Disable texture filtering
+        image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..default()
+        });
+        let texture = app.world.resource_mut::<Assets<Image>>().add(image);
+
+        let [width, height] = ATLAS_SIZE;
+        let mut layout = TextureAtlas::new_empty(texture, Vec2::new(width as f32, height as f32));
+        for (_, [x, y, width, height]) in REGIONS {
+            let min = Vec2::new(*x as f32, *y as f32);
+            layout.add_texture(Rect {
+                min,
+                max: min + Vec2::new(*width as f32, *height as f32),
+            });
+        }
+        let layout = app.world.resource_mut::<Assets<TextureAtlas>>().add(layout);
+
+        app.insert_resource(Atlas::from_index(layout))
+            .add_system(animate);
+
+        #[cfg(feature = "hot_reload")]
+        app.add_plugin(crate::aseprite::AsepritePlugin);
+    }
+}
+
+/// Plays the frames of an aseprite file on the sprite, with the durations set in Aseprite.
+///
+/// The frames are looked up every time, so a reloaded file plays its new timing right away.
+#[derive(Component, Clone)]
+pub struct Animation {
+    file: &'static str,
+    /// Played one after another, pictures drawn as layers of a single frame animate this way
+    layers: Vec<&'static str>,
+    tag: Option<&'static str>,
+    once: bool,
+    /// How fast it plays, 0 holds the current frame
+    pub speed: f32,
+    elapsed: f32,
+}
+
+impl Animation {
+    pub fn new(file: &'static str, layers: impl Into<Vec<&'static str>>) -> Self {
+        Animation {
+            file,
+            layers: layers.into(),
+            tag: None,
+            once: false,
+            speed: 1.0,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Only the frames of the tag play, in its direction
+    pub fn tag(self, tag: &'static str) -> Self {
+        Animation {
+            tag: Some(tag),
+            ..self
+        }
+    }
+
+    /// Stops on the last frame instead of looping
+    pub fn once(self) -> Self {
+        Animation { once: true, ..self }
+    }
+
+    /// Starts over as `other`, the same animation in another color for example
+    pub fn switch_to(&mut self, other: &Animation) {
+        if self.file != other.file || self.layers != other.layers || self.tag != other.tag {
+            *self = Animation {
+                speed: self.speed,
+                ..other.clone()
+            };
+        }
+    }
+}
+
+fn animate(
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    atlas: Res<Atlas>,
+    mut sprites: Query<(&mut Animation, &mut TextureAtlasSprite)>,
+    mut warned: Local<HashSet<(&'static str, &'static str)>>,
+) {
+    // The pause menu stops the run, the pictures included
+    if matches!(state.current(), AppState::Paused | AppState::Settings) {
+        return;
+    }
+    for (mut animation, mut sprite) in sprites.iter_mut() {
+        let Some(file) = atlas.files.get(animation.file) else {continue};
+        if let Some(tag) = animation.tag {
+            if !file.tags.contains_key(tag) && warned.insert((animation.file, tag)) {
+                warn!("{} has no tag {tag}, all its frames play", animation.file);
+            }
+        }
+        let frames: Vec<_> = animation
+            .layers
+            .iter()
+            .flat_map(|layer| file.frames(layer, animation.tag))
+            .collect();
+        let total: f32 = frames.iter().map(|(_, secs)| secs).sum();
+        if frames.is_empty() || total <= 0.0 {
+            continue;
+        }
+
+        animation.elapsed += time.delta_seconds() * animation.speed;
+        if !animation.once {
+            animation.elapsed %= total;
+        }
+        let mut left = animation.elapsed;
+        let (index, _) = frames
+            .iter()
+            .find(|(_, secs)| {
+                left -= secs;
+                left < 0.0
+            })
+            .unwrap_or(&frames[frames.len() - 1]);
+        if sprite.index != *index {
+            sprite.index = *index;
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Minimal WebSocket relay for online co-op: every text message from one
+//! client is forwarded to all the other clients.
+//!
+//! `cargo run --bin relay -- 127.0.0.1:9001`
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use std::{
+        net::TcpListener,
+        sync::{
+            mpsc::{channel, Sender},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+    use tungstenite::Message;
+
+    type Clients = Arc<Mutex<Vec<(usize, Sender<String>)>>>;
+
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9001".to_string());
+    let listener = TcpListener::bind(&address).expect("address to bind the relay to");
+    println!("Relay listening on ws://{address}");
+
+    let clients: Clients = Default::default();
+
+    for (id, stream) in listener.incoming().enumerate() {
+        let Ok(stream) = stream else { continue };
+        let clients = clients.clone();
+        thread::spawn(move || {
+            // The handshake waits for the whole request, only the relaying loop polls
+            let mut socket = match tungstenite::accept(stream) {
+                Ok(socket) => socket,
+                Err(e) => {
+                    eprintln!("Client {id} failed the handshake: {e}");
+                    return;
+                }
+            };
+            socket
+                .get_ref()
+                .set_read_timeout(Some(Duration::from_millis(5)))
+                .expect("non-zero timeout");
+            println!("Client {id} connected");
+
+            let (sender, to_socket) = channel();
+            clients.lock().unwrap().push((id, sender));
+
+            let broadcast = |text: String| {
+                for (other, sender) in clients.lock().unwrap().iter() {
+                    if *other != id {
+                        let _ = sender.send(text.clone());
+                    }
+                }
+            };
+
+            loop {
+                let mut alive = to_socket
+                    .try_iter()
+                    .all(|text| socket.write_message(Message::Text(text)).is_ok());
+                match socket.read_message() {
+                    Ok(Message::Text(text)) => broadcast(text),
+                    Ok(Message::Close(_)) => alive = false,
+                    Ok(_) => {}
+                    Err(tungstenite::Error::Io(e))
+                        if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => {}
+                    Err(_) => alive = false,
+                }
+                if !alive {
+                    break;
+                }
+            }
+
+            clients.lock().unwrap().retain(|(other, _)| *other != id);
+            println!("Client {id} disconnected");
+        });
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
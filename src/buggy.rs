@@ -1,66 +1,245 @@
 use crate::{
+    atlas::Animation,
     harvester::Helium,
+    i18n::Localization,
+    net::{Mirrored, NetRole},
+    picking::Clickable,
     terrain::{TerrainMarker, TerrainSprite},
     tooltip::TooltipString,
-    util::image_from_aseprite,
+    util::{PanelAssetHandlers, TerrainAssetHandlers},
     AppState, HEIGHT, PIXEL_MULTIPLIER, WIDTH,
 };
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PLAYERS: usize = 2;
+
+/// Speed in pixels per second at which the wheels turn as fast as they are drawn
+const WHEEL_SPEED: f32 = 300.0;
+/// Extra room around the buggies when the camera frames all of them
+const CAMERA_FRAME_MARGIN: Vec2 = Vec2 {
+    x: 60.0 * PIXEL_MULTIPLIER,
+    y: 40.0 * PIXEL_MULTIPLIER,
+};
 
 #[derive(Component)]
-pub struct Buggy {}
-
-pub fn setup_buggy(mut commands: Commands, mut textures: ResMut<Assets<Image>>) {
-    let buggy_image =
-        image_from_aseprite(include_bytes!("../assets/spritebuggy3.aseprite"), "Layer 1");
-    let size = buggy_image.size() * PIXEL_MULTIPLIER;
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(size),
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3 {
-                    z: 0.9,
-                    y: 26.0 * PIXEL_MULTIPLIER,
-                    x: 150.0 * PIXEL_MULTIPLIER,
+pub struct Buggy {
+    pub player: usize,
+}
+
+/// Buggy driven from this machine, the value picks the key set in `KeyBindings`
+#[derive(Component)]
+pub struct LocalControl(pub usize);
+
+/// What the driver is pressing right now, filled from the keyboard or from the network
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuggyInput {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// How many buggies are spawned when the game starts, toggled on the start screen
+#[derive(Resource)]
+pub struct LocalPlayers(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuggyControls {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    /// Collect, repair or unload at whatever is in reach
+    pub interact: KeyCode,
+}
+
+pub const CONTROLS: [BuggyControls; MAX_PLAYERS] = [
+    BuggyControls {
+        forward: KeyCode::W,
+        back: KeyCode::S,
+        left: KeyCode::A,
+        right: KeyCode::D,
+        interact: KeyCode::E,
+    },
+    BuggyControls {
+        forward: KeyCode::Up,
+        back: KeyCode::Down,
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        interact: KeyCode::RControl,
+    },
+];
+
+impl BuggyControls {
+    pub fn keys(&self) -> [KeyCode; 5] {
+        [
+            self.forward,
+            self.back,
+            self.left,
+            self.right,
+            self.interact,
+        ]
+    }
+
+    pub fn keys_mut(&mut self) -> [&mut KeyCode; 5] {
+        [
+            &mut self.forward,
+            &mut self.back,
+            &mut self.left,
+            &mut self.right,
+            &mut self.interact,
+        ]
+    }
+}
+
+/// Keys of every local player, `CONTROLS` unless changed in the settings
+#[derive(Resource, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBindings(pub [BuggyControls; MAX_PLAYERS]);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings(CONTROLS)
+    }
+}
+
+/// Tint used to tell the buggies apart on the terrain and on the panel map
+pub fn player_color(player: usize) -> Color {
+    match player {
+        0 => Color::WHITE,
+        1 => Color::rgb(0.6, 0.8, 1.0),
+        2 => Color::rgb(1.0, 0.7, 0.6),
+        _ => Color::rgb(0.7, 1.0, 0.6),
+    }
+}
+
+/// The player number only matters with more than one buggy around
+pub fn update_buggy_tooltips(
+    localization: Res<Localization>,
+    panel_assets: Res<PanelAssetHandlers>,
+    mut buggies: Query<(&Buggy, &Helium, &mut TooltipString)>,
+) {
+    let multiplayer = buggies.iter().count() > 1;
+    for (buggy, helium, mut string) in buggies.iter_mut() {
+        let text = if multiplayer {
+            localization.format(
+                "buggy-player-helium",
+                &[
+                    ("player", (buggy.player + 1).into()),
+                    ("helium", helium.0.into()),
+                ],
+            )
+        } else {
+            localization.format("buggy-helium", &[("helium", helium.0.into())])
+        };
+        if string.sections.first().map(|(text, _)| text) != Some(&text) {
+            *string = TooltipString::text(text)
+                .with_icon(&panel_assets.buggy_icon, player_color(buggy.player));
+        }
+    }
+}
+
+pub fn spawn_buggy(
+    commands: &mut Commands,
+    terrain_assets: &TerrainAssetHandlers,
+    player: usize,
+) -> Entity {
+    commands
+        .spawn((
+            SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    color: player_color(player),
+                    ..terrain_assets.buggy.sprite()
                 },
+                transform: Transform {
+                    translation: Vec3 {
+                        z: 0.9,
+                        y: (26.0 - 20.0 * player as f32) * PIXEL_MULTIPLIER,
+                        x: 150.0 * PIXEL_MULTIPLIER,
+                    },
+                    ..default()
+                },
+                texture_atlas: terrain_assets.buggy.atlas.clone(),
                 ..default()
             },
-            texture: textures.add(buggy_image),
-            ..default()
-        },
-        Buggy {},
-        RigidBody::Dynamic,
-        Damping {
-            angular_damping: 0.96,
-            linear_damping: 0.1,
-        },
-        Collider::cuboid(6.0 * PIXEL_MULTIPLIER, 10.0 * PIXEL_MULTIPLIER),
-        ColliderMassProperties::Density(2.0),
-        Velocity::default(),
-        ExternalForce::default(),
-        Helium(0),
-        TooltipString("Helium amount: 0".to_string()),
-        TerrainMarker,
-    ));
+            Buggy { player },
+            BuggyInput::default(),
+            RigidBody::Dynamic,
+            Damping {
+                angular_damping: 0.96,
+                linear_damping: 0.1,
+            },
+            Collider::cuboid(6.0 * PIXEL_MULTIPLIER, 10.0 * PIXEL_MULTIPLIER),
+            ColliderMassProperties::Density(2.0),
+            // Bumps are heard
+            ActiveEvents::COLLISION_EVENTS,
+            Velocity::default(),
+            ExternalForce::default(),
+            Helium(0),
+            Clickable,
+            // Filled in by `update_buggy_tooltips`
+            TooltipString::default(),
+            terrain_assets.wheels.clone(),
+            TerrainMarker,
+        ))
+        .id()
 }
 
-#[allow(clippy::type_complexity)]
-pub fn buggy_movement_and_control(
-    mut buggy: Query<
-        (&mut Velocity, &mut ExternalForce, &Transform),
-        (With<Buggy>, Without<Camera2d>),
-    >,
-    mut camera: Query<&mut Transform, (With<TerrainMarker>, With<Camera2d>)>,
+pub fn spin_wheels(mut buggies: Query<(&Velocity, &mut Animation), With<Buggy>>) {
+    for (velocity, mut wheels) in buggies.iter_mut() {
+        wheels.speed = velocity.linvel.length() / WHEEL_SPEED;
+    }
+}
+
+pub fn setup_buggy(
+    mut commands: Commands,
+    terrain_assets: Res<TerrainAssetHandlers>,
+    players: Res<LocalPlayers>,
+    role: Res<NetRole>,
+) {
+    // Clients get their buggies from the host's snapshots
+    if !role.is_authoritative() {
+        return;
+    }
+    for player in 0..players.0 {
+        let buggy = spawn_buggy(&mut commands, &terrain_assets, player);
+        commands.entity(buggy).insert(LocalControl(player));
+    }
+}
+
+pub fn read_local_input(
+    mut buggies: Query<(&LocalControl, &mut BuggyInput)>,
     keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
     state: Res<State<AppState>>,
-    terrain: Query<&Sprite, With<TerrainSprite>>,
 ) {
-    let mut position = None;
+    for (control, mut input) in buggies.iter_mut() {
+        let controls = &bindings.0[control.0.min(MAX_PLAYERS - 1)];
+        let new_input = match state.current() {
+            AppState::Terrain => BuggyInput {
+                forward: keys.pressed(controls.forward),
+                back: keys.pressed(controls.back),
+                left: keys.pressed(controls.left),
+                right: keys.pressed(controls.right),
+            },
+            _ => BuggyInput::default(),
+        };
+        // Avoid tripping change detection every frame, the network code relies on it
+        if *input != new_input {
+            *input = new_input;
+        }
+    }
+}
 
+/// The host drives every buggy, the mirrored ones keep the velocity of their snapshots
+#[allow(clippy::type_complexity)]
+pub fn buggy_movement_and_control(
+    mut buggies: Query<
+        (&BuggyInput, &mut Velocity, &mut ExternalForce, &Transform),
+        Without<Mirrored>,
+    >,
+) {
     let friction = 400.0;
     let max_turn_vel = 3.0;
     let turn_vel = 0.5;
@@ -69,7 +248,7 @@ pub fn buggy_movement_and_control(
     let steering_centering_vel = 0.3;
     let breaking_power = 30_000.0;
 
-    if let Ok((mut vel, mut force, pos)) = buggy.get_single_mut() {
+    for (input, mut vel, mut force, pos) in buggies.iter_mut() {
         let buggy_side = pos.rotation
             * Vec3 {
                 x: 1.0,
@@ -90,24 +269,22 @@ pub fn buggy_movement_and_control(
 
         let mut acceleration = 0.0;
         force.force = Vec2::default();
-        if state.current() == &AppState::Terrain {
-            if keys.pressed(KeyCode::W) {
-                acceleration = horse_power_fwd;
-            }
-            if keys.pressed(KeyCode::S) {
-                if forward_vel > 0.0 {
-                    acceleration = -breaking_power
-                } else {
-                    acceleration = -horse_power_back;
-                }
-            }
-            if keys.pressed(KeyCode::A) {
-                vel.angvel = (vel.angvel + turn_vel).min(turn_force);
-            }
-            if keys.pressed(KeyCode::D) {
-                vel.angvel = (vel.angvel - turn_vel).max(-turn_force);
+        if input.forward {
+            acceleration = horse_power_fwd;
+        }
+        if input.back {
+            if forward_vel > 0.0 {
+                acceleration = -breaking_power
+            } else {
+                acceleration = -horse_power_back;
             }
         }
+        if input.left {
+            vel.angvel = (vel.angvel + turn_vel).min(turn_force);
+        }
+        if input.right {
+            vel.angvel = (vel.angvel - turn_vel).max(-turn_force);
+        }
         force.force += buggy_heading.truncate() * acceleration;
 
         let lateral_force = vel.linvel.project_onto(buggy_side.truncate());
@@ -118,27 +295,34 @@ pub fn buggy_movement_and_control(
         }
 
         force.force += lateral_friction;
-        position = Some(pos.translation);
     }
+}
 
-    if let Some(pos) = position {
-        let terrain = terrain.single().custom_size.unwrap();
-        camera.get_single_mut().unwrap().translation.x = if pos.x < -(terrain.x / 2.0 - WIDTH / 2.0)
-        {
-            -(terrain.x / 2.0 - WIDTH / 2.0)
-        } else if pos.x > (terrain.x / 2.0 - WIDTH / 2.0) {
-            terrain.x / 2.0 - WIDTH / 2.0
-        } else {
-            pos.x
-        };
-        camera.get_single_mut().unwrap().translation.y =
-            if pos.y < -(terrain.y / 2.0 - HEIGHT / 2.0) {
-                -(terrain.y / 2.0 - HEIGHT / 2.0)
-            } else if pos.y > (terrain.y / 2.0 - HEIGHT / 2.0) {
-                terrain.y / 2.0 - HEIGHT / 2.0
-            } else {
-                pos.y
-            };
-        camera.get_single_mut().unwrap().translation.z = 100.0;
-    }
+/// Keeps every local buggy in view, zooming out when they drive apart
+#[allow(clippy::type_complexity)]
+pub fn frame_buggies(
+    buggies: Query<&Transform, (With<LocalControl>, Without<Camera2d>)>,
+    mut camera: Query<
+        (&mut Transform, &mut OrthographicProjection),
+        (With<TerrainMarker>, With<Camera2d>),
+    >,
+    terrain: Query<&TextureAtlasSprite, With<TerrainSprite>>,
+) {
+    let mut positions = buggies.iter().map(|t| t.translation.truncate());
+    let Some(first) = positions.next() else {return};
+    let (min, max) = positions.fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+    let Ok((mut camera, mut projection)) = camera.get_single_mut() else {return};
+    let terrain = terrain.single().custom_size.unwrap();
+
+    let screen = Vec2::new(WIDTH, HEIGHT);
+    let needed = (max - min + CAMERA_FRAME_MARGIN) / screen;
+    let max_scale = (terrain / screen).min_element();
+    let scale = needed.max_element().clamp(1.0, max_scale);
+    projection.scale = scale;
+
+    let half_view = screen * scale / 2.0;
+    let limit = terrain / 2.0 - half_view;
+    let center = ((min + max) / 2.0).clamp(-limit, limit);
+
+    camera.translation = center.extend(100.0);
 }
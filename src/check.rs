@@ -0,0 +1,159 @@
+//! `cargo run -- --check-assets` looks up every sprite and slice the game draws
+//! and compares the sprites with the sizes the code is laid out for,
+//! so broken art shows up before the game ships instead of as a panic.
+
+use std::cell::RefCell;
+
+use crate::{
+    atlas::{Animation, Art, Atlas, Region, Slice},
+    terrain::TERRAIN_SIZE,
+    util::sprites,
+};
+
+use super::*;
+
+/// Canvas size of every file in pixels
+fn expected_size(file: &str) -> Option<Vec2> {
+    let size = match file {
+        // Every layer of the panel is drawn over the whole screen
+        "spritepanel8.aseprite" => Vec2::new(WIDTH, HEIGHT) / PIXEL_MULTIPLIER,
+        // The colliders around the edges are placed by `TERRAIN_SIZE`
+        "spritemap5.aseprite" => Vec2::new(TERRAIN_SIZE.0, TERRAIN_SIZE.1) / PIXEL_MULTIPLIER,
+        "spriteendgood.aseprite" | "spriteendbad.aseprite" => Vec2::new(440.0, 320.0),
+        // The lamp is drawn over the center, the harvester circles it in the next cells
+        "spritecenter1.aseprite" | "spriteharvester1.aseprite" => Vec2::new(25.0, 25.0),
+        "spritebuggy3.aseprite" => Vec2::new(20.0, 20.0),
+        "spritebutton2.aseprite" => Vec2::new(11.0, 14.0),
+        // Covers the cell of the harvester and the ones around it, which can't be built on
+        "iconcenter3.aseprite" => Vec2::new(31.0, 31.0),
+        "iconbuggy1.aseprite" | "iconharvest1.aseprite" | "iconstate1.aseprite" => {
+            Vec2::new(5.0, 5.0)
+        }
+        "iconship1.aseprite" => Vec2::new(11.0, 9.0),
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// Writes down every name the game asks for, next to what the atlas has
+struct Checker {
+    atlas: Atlas,
+    report: RefCell<Vec<(String, Result<String, String>)>>,
+}
+
+impl Art for Checker {
+    fn region(&self, name: &str) -> Region {
+        let region = self.atlas.find_region(name);
+        let line = match &region {
+            None => Err("missing".to_string()),
+            Some(region) => {
+                let size = region.size / PIXEL_MULTIPLIER;
+                let file = name.split('#').next().unwrap_or_default();
+                let found = format!("{}x{}", size.x, size.y);
+                match expected_size(file) {
+                    Some(expected) if expected == size => Ok(found),
+                    Some(expected) => {
+                        Err(format!("{found}, expected {}x{}", expected.x, expected.y))
+                    }
+                    None => Err(format!("{found}, no size expected for {file}")),
+                }
+            }
+        };
+        self.report.borrow_mut().push((name.to_string(), line));
+        region.unwrap_or(Region {
+            atlas: default(),
+            index: 0,
+            size: Vec2::ZERO,
+        })
+    }
+
+    fn slice(&self, name: &str) -> Slice {
+        let slice = self.atlas.find_slice(name);
+        let line = match slice {
+            None => Err("missing slice".to_string()),
+            Some(slice) => {
+                let size = slice.size() / PIXEL_MULTIPLIER;
+                Ok(format!("slice {}x{}", size.x, size.y))
+            }
+        };
+        self.report.borrow_mut().push((name.to_string(), line));
+        slice.unwrap_or_default()
+    }
+
+    fn animation(
+        &self,
+        file: &'static str,
+        layers: &[&'static str],
+        tag: Option<&'static str>,
+    ) -> Animation {
+        let frames = self.atlas.files.get(file);
+        let count = |n: usize| match n {
+            1 => "1 frame".to_string(),
+            n => format!("{n} frames"),
+        };
+        for layer in layers {
+            let line = match frames.and_then(|frames| Some((frames, frames.layer(layer)?))) {
+                None => Err("missing".to_string()),
+                Some((frames, regions)) => match tag {
+                    None => Ok(count(regions.len())),
+                    Some(tag) => match frames.tags.get(tag) {
+                        Some(found) if *found.frames.end() < regions.len() => {
+                            Ok(format!("{} of {tag}", count(found.frames.clone().count())))
+                        }
+                        Some(_) => Err(format!("tag {tag} runs past the last frame")),
+                        None => Err(format!("no tag {tag}")),
+                    },
+                },
+            };
+            self.report
+                .borrow_mut()
+                .push((format!("{file}#{layer} animation"), line));
+        }
+        self.atlas.animation(file, layers, tag)
+    }
+}
+
+/// Every name the game asks for with what was found, or what's wrong with it
+fn report() -> Vec<(String, Result<String, String>)> {
+    let checker = Checker {
+        atlas: Atlas::from_index(default()),
+        report: default(),
+    };
+    sprites(&checker);
+    checker.report.into_inner()
+}
+
+/// Prints the report, the exit code is 1 when something is off
+pub fn check_assets() -> i32 {
+    let report = report();
+    let width = report.iter().map(|(name, _)| name.len()).max().unwrap_or(0) + 2;
+    let mut problems = 0;
+    for (name, line) in &report {
+        match line {
+            Ok(found) => println!("  {name:<width$}{found}"),
+            Err(problem) => {
+                problems += 1;
+                println!("! {name:<width$}{problem}");
+            }
+        }
+    }
+    println!(
+        "{} sprites, slices and animations checked, {problems} problems",
+        report.len()
+    );
+    i32::from(problems > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn art_has_everything_the_game_draws() {
+        let problems: Vec<_> = report()
+            .into_iter()
+            .filter(|(_, line)| line.is_err())
+            .collect();
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    harvester::StoredCanisters,
+    scaling::UiCamera,
+    terrain::{Base, TerrainMarker, CANISTERS_TO_WIN},
+    util::PanelAssetHandlers,
+};
+
+use super::*;
+
+/// Seconds for the ship to come down next to the base
+const LANDING_TIME: f32 = 3.0;
+/// Seconds for every canister to be carried into the ship
+const LOADING_TIME_PER_CANISTER: f32 = 0.6;
+/// Seconds for the ship to leave the screen
+const DEPARTURE_TIME: f32 = 3.0;
+/// Seconds before the cutscene can be skipped, the click that made the last canister is still down
+const SKIP_DELAY: f32 = 0.5;
+/// How much bigger the ship is compared to its panel icon
+const SHIP_SCALE: f32 = 3.0;
+
+#[derive(Component)]
+struct CutsceneShip;
+
+#[derive(Component)]
+struct CutsceneCanister {
+    index: usize,
+    rack_position: Vec3,
+}
+
+#[derive(Resource)]
+struct Cutscene {
+    elapsed: f32,
+    /// Whether enough canisters were made for the ship to take the player on board
+    boarding: bool,
+    canisters: usize,
+    landing_spot: Vec3,
+}
+
+impl Cutscene {
+    fn loading_time(&self) -> f32 {
+        self.canisters as f32 * LOADING_TIME_PER_CANISTER
+    }
+
+    fn total_time(&self) -> f32 {
+        match self.boarding {
+            true => LANDING_TIME + self.loading_time() + DEPARTURE_TIME,
+            // The ship doesn't stop and just flies over
+            false => LANDING_TIME + DEPARTURE_TIME,
+        }
+    }
+}
+
+pub struct CutscenePlugin;
+
+impl Plugin for CutscenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::Cutscene).with_system(spawn_cutscene))
+            .add_system_set(SystemSet::on_update(AppState::Cutscene).with_system(play_cutscene));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn spawn_cutscene(
+    mut commands: Commands,
+    stored_canisters: Res<StoredCanisters>,
+    panel_assets: Res<PanelAssetHandlers>,
+    base: Query<&Transform, With<Base>>,
+    mut terrain_cam: Query<(&mut Camera, &mut Transform), (With<TerrainMarker>, Without<Base>)>,
+    mut other_cams: Query<&mut Camera, (Without<TerrainMarker>, Without<UiCamera>)>,
+) {
+    let base = base.single().translation;
+
+    // Show the base, wherever the player was looking
+    other_cams.for_each_mut(|mut c| c.is_active = false);
+    for (mut camera, mut transform) in terrain_cam.iter_mut() {
+        camera.is_active = true;
+        transform.translation.x = base.x - WIDTH / 4.0;
+        transform.translation.y = base.y;
+    }
+
+    let landing_spot = Vec3 {
+        x: base.x - 50.0 * PIXEL_MULTIPLIER,
+        y: base.y,
+        z: 10.0,
+    };
+    let ship = &panel_assets.ship;
+    commands.spawn((
+        SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                custom_size: Some(ship.size * SHIP_SCALE),
+                ..ship.sprite()
+            },
+            transform: Transform::from_translation(landing_spot + Vec3::Y * HEIGHT),
+            ..ship.sprite_sheet()
+        },
+        CutsceneShip,
+        TerrainMarker,
+    ));
+
+    // Canisters wait in a row next to the rack at the base
+    for index in 0..stored_canisters.0 {
+        let rack_position = Vec3 {
+            x: base.x - (12.0 + 5.0 * index as f32) * PIXEL_MULTIPLIER,
+            y: base.y - 25.0 * PIXEL_MULTIPLIER,
+            z: 9.0,
+        };
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::SEA_GREEN,
+                    custom_size: Some(Vec2::new(3.0, 6.0) * PIXEL_MULTIPLIER),
+                    ..default()
+                },
+                transform: Transform::from_translation(rack_position),
+                ..default()
+            },
+            CutsceneCanister {
+                index,
+                rack_position,
+            },
+            TerrainMarker,
+        ));
+    }
+
+    commands.insert_resource(Cutscene {
+        elapsed: 0.0,
+        boarding: stored_canisters.0 >= CANISTERS_TO_WIN,
+        canisters: stored_canisters.0,
+        landing_spot,
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn play_cutscene(
+    mut cutscene: ResMut<Cutscene>,
+    time: Res<Time>,
+    mut ship: Query<&mut Transform, (With<CutsceneShip>, Without<CutsceneCanister>)>,
+    mut canisters: Query<(&CutsceneCanister, &mut Transform, &mut Visibility)>,
+    mut app_state: ResMut<State<AppState>>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+) {
+    cutscene.elapsed += time.delta_seconds();
+    let t = cutscene.elapsed;
+    let landing_spot = cutscene.landing_spot;
+    let above = landing_spot + Vec3::Y * HEIGHT;
+    let away = landing_spot + Vec3::new(-WIDTH, HEIGHT * 1.5, 0.0);
+
+    let mut ship = ship.single_mut();
+    ship.translation = if !cutscene.boarding {
+        let passed = (t / (LANDING_TIME + DEPARTURE_TIME)).min(1.0);
+        above.lerp(away - Vec3::Y * HEIGHT, passed)
+    } else if t < LANDING_TIME {
+        above.lerp(landing_spot, ease(t / LANDING_TIME))
+    } else if t < LANDING_TIME + cutscene.loading_time() {
+        landing_spot
+    } else {
+        let departing = (t - LANDING_TIME - cutscene.loading_time()) / DEPARTURE_TIME;
+        landing_spot.lerp(away, ease(departing.min(1.0)))
+    };
+
+    if cutscene.boarding {
+        for (canister, mut transform, mut visibility) in canisters.iter_mut() {
+            let start = LANDING_TIME + canister.index as f32 * LOADING_TIME_PER_CANISTER;
+            let carried = ((t - start) / LOADING_TIME_PER_CANISTER).clamp(0.0, 1.0);
+            transform.translation = canister.rack_position.lerp(landing_spot, carried);
+            visibility.is_visible = carried < 1.0;
+        }
+    }
+
+    let skipped = t >= SKIP_DELAY
+        && (keys.any_just_pressed([KeyCode::Space, KeyCode::Escape])
+            || buttons.just_pressed(MouseButton::Left));
+    if skipped || t >= cutscene.total_time() {
+        app_state.set(AppState::Finish).unwrap();
+    }
+}
+
+fn ease(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// Things that happen during a run, for whoever wants to keep track of them
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum GameEvent {
+    /// A harvester brought one unit of helium to its center
+    HeliumHarvested {
+        slot: usize,
+    },
+    HarvesterFull {
+        slot: usize,
+    },
+    HarvesterBroke {
+        slot: usize,
+    },
+    HarvesterRepaired {
+        player: usize,
+        slot: usize,
+    },
+    HeliumCollected {
+        player: usize,
+        slot: usize,
+        amount: usize,
+    },
+    HeliumDelivered {
+        player: usize,
+        amount: usize,
+    },
+    /// Main storage can't take any more helium
+    StorageFull,
+    HarvesterBuilt {
+        slot: usize,
+    },
+    CanisterCrafted {
+        index: usize,
+    },
+}
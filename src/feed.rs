@@ -0,0 +1,128 @@
+use bevy::text::Text2dBounds;
+
+use crate::{
+    events::GameEvent,
+    i18n::Localization,
+    panel::{PanelMarker, PANEL_OFFSET},
+    start::EndTimer,
+    stats::{format_time, in_run},
+    toast::Toast,
+};
+
+use super::*;
+
+/// Lines kept on the panel screen, older ones scroll away
+const LOG_LINES: usize = 9;
+
+/// Everything worth knowing that happened in this run, newest last
+#[derive(Resource, Default)]
+struct EventLog(Vec<String>);
+
+#[derive(Component)]
+struct EventLogText;
+
+pub struct EventFeedPlugin;
+
+impl Plugin for EventFeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>()
+            .add_system_set(SystemSet::on_exit(AppState::Start).with_system(set_up_log))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_run)
+                    .with_system(record_events),
+            );
+    }
+}
+
+/// `None` for events that happen too often to be worth reading about
+fn describe(event: &GameEvent, localization: &Localization) -> Option<String> {
+    let harvester = |id, slot: usize| localization.format(id, &[("slot", (slot + 1).into())]);
+    Some(match *event {
+        GameEvent::HarvesterBroke { slot } => harvester("event-harvester-broke", slot),
+        GameEvent::HarvesterFull { slot } => harvester("event-harvester-full", slot),
+        GameEvent::HarvesterRepaired { slot, .. } => harvester("event-harvester-fixed", slot),
+        GameEvent::HarvesterBuilt { slot } => harvester("event-harvester-built", slot),
+        GameEvent::StorageFull => localization.get("event-storage-full"),
+        GameEvent::CanisterCrafted { index } => {
+            localization.format("event-canister-crafted", &[("index", (index + 1).into())])
+        }
+        GameEvent::HeliumHarvested { .. }
+        | GameEvent::HeliumCollected { .. }
+        | GameEvent::HeliumDelivered { .. } => return None,
+    })
+}
+
+/// Whether the player should hear about it even while driving
+fn is_alert(event: &GameEvent) -> bool {
+    matches!(
+        event,
+        GameEvent::HarvesterBroke { .. } | GameEvent::HarvesterFull { .. } | GameEvent::StorageFull
+    )
+}
+
+fn set_up_log(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+    commands.insert_resource(EventLog::default());
+
+    let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
+    // FIXME (samoylovfp) deduplicate
+    let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
+
+    // The screen in the top right corner of the panel
+    let screen_corner = Vec2::new(115.0, 5.0) * PIXEL_MULTIPLIER;
+    let screen_size = Vec2::new(39.0, 29.0) * PIXEL_MULTIPLIER;
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: font_handle,
+                    font_size: 8.0,
+                    color: Color::rgb(0.1, 0.1, 0.2),
+                },
+            )
+            .with_alignment(TextAlignment::TOP_LEFT),
+            text_2d_bounds: Text2dBounds { size: screen_size },
+            transform: Transform::from_xyz(
+                PANEL_OFFSET.x - WIDTH / 2.0 + screen_corner.x,
+                PANEL_OFFSET.y + HEIGHT / 2.0 - screen_corner.y,
+                4.0,
+            ),
+            ..default()
+        },
+        EventLogText,
+        PanelMarker,
+    ));
+}
+
+fn record_events(
+    mut events: EventReader<GameEvent>,
+    mut log: ResMut<EventLog>,
+    timer: Query<&EndTimer>,
+    state: Res<State<AppState>>,
+    mut toasts: EventWriter<Toast>,
+    mut text: Query<&mut Text, With<EventLogText>>,
+    localization: Res<Localization>,
+) {
+    let time = format_time(timer.get_single().map_or(0.0, |t| t.timer.elapsed_secs()));
+    for event in events.iter() {
+        let Some(message) = describe(event, &localization) else {continue};
+        if is_alert(event) && *state.current() == AppState::Terrain {
+            toasts.send(Toast(localization.format(
+                "event-alert",
+                &[
+                    ("message", message.as_str().into()),
+                    ("time", time.as_str().into()),
+                ],
+            )));
+        }
+        log.0.push(format!("{time} {message}"));
+    }
+
+    if log.is_changed() {
+        let shown = &log.0[log.0.len().saturating_sub(LOG_LINES)..];
+        for mut text in text.iter_mut() {
+            text.sections[0].value = shown.join("\n");
+        }
+    }
+}
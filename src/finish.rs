@@ -2,9 +2,13 @@ use bevy::text::Text2dBounds;
 
 use crate::{
     harvester::StoredCanisters,
+    highscore::{HighScores, NameEntry, MAX_NAME_LENGTH},
+    i18n::Localization,
     panel::PanelMarker,
+    stats::{export_stats, RunStats},
     terrain::{TerrainMarker, CANISTERS_TO_WIN},
-    util::img_handle_and_size_from_bytes,
+    tutorial::TutorialRun,
+    util::EndingAssetHandlers,
 };
 
 use super::*;
@@ -12,6 +16,12 @@ use super::*;
 #[derive(Component)]
 pub struct FinishMarker;
 
+#[derive(Component)]
+struct ExportHint;
+
+#[derive(Component)]
+struct NameText;
+
 pub struct Finish;
 
 impl Plugin for Finish {
@@ -22,7 +32,14 @@ impl Plugin for Finish {
                 .with_system(spawn_finish),
         );
         app.add_system_set(
-            SystemSet::on_exit(AppState::Finish).with_system(despawn_really_everything),
+            SystemSet::on_exit(AppState::Finish)
+                .with_system(despawn_really_everything)
+                .with_system(drop_name_entry),
+        );
+        app.add_system_set(
+            SystemSet::on_update(AppState::Finish)
+                .with_system(handle_export)
+                .with_system(handle_name_entry),
         );
     }
 }
@@ -40,38 +57,28 @@ fn despawn_really_everything(mut commands: Commands, entities: Query<Entity>) {
     entities.for_each(|e| commands.entity(e).despawn());
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_finish(
     mut commands: Commands,
     tanks: Res<StoredCanisters>,
-    mut textures: ResMut<Assets<Image>>,
+    stats: Res<RunStats>,
+    high_scores: Res<HighScores>,
+    tutorial_run: Option<Res<TutorialRun>>,
+    endings: Res<EndingAssetHandlers>,
     mut fonts: ResMut<Assets<Font>>,
+    localization: Res<Localization>,
 ) {
     let picture;
     let text;
     if tanks.0 >= CANISTERS_TO_WIN {
-        picture = img_handle_and_size_from_bytes(
-            include_bytes!("../assets/spriteendgood.aseprite"),
-            "Layer 1",
-            &mut textures,
-        );
-        text = include_str!("win.txt");
+        picture = endings.win.clone();
+        text = localization.get("ending-win");
     } else {
-        picture = img_handle_and_size_from_bytes(
-            include_bytes!("../assets/spriteendbad.aseprite"),
-            "Layer 1",
-            &mut textures,
-        );
-        text = include_str!("lose.txt");
+        picture = endings.lose.clone();
+        text = localization.get("ending-lose");
     }
-    commands.spawn(Camera2dBundle::default());
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            custom_size: Some(picture.1),
-            ..default()
-        },
-        texture: picture.0,
-        ..default()
-    });
+    commands.spawn((Camera2dBundle::default(), FinishMarker));
+    commands.spawn((picture.sprite_sheet(), FinishMarker));
     let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
     // FIXME (samoylovfp) deduplicate
     let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
@@ -79,20 +86,136 @@ fn spawn_finish(
     let box_size = Vec2::new(600.0, 600.0);
     let box_position = Vec2::new(0.0, -250.0);
     let text_style = TextStyle {
-        font: font_handle,
+        font: font_handle.clone(),
         font_size: 20.0,
         color: Color::WHITE,
     };
+    let stats_style = TextStyle {
+        font: font_handle.clone(),
+        font_size: 10.0,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_sections([
+                TextSection::new(stats.summary(&localization), stats_style.clone()),
+                TextSection::new(
+                    format!("\n\n{}", localization.get("export-hint")),
+                    stats_style.clone(),
+                ),
+            ])
+            .with_alignment(TextAlignment::TOP_LEFT),
+            transform: Transform::from_xyz(-WIDTH / 2.0 + 10.0, HEIGHT / 2.0 - 10.0, 1.0),
+            ..default()
+        },
+        ExportHint,
+        FinishMarker,
+    ));
+
+    if tutorial_run.is_none() && high_scores.qualifies(stats.score) {
+        commands.init_resource::<NameEntry>();
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_sections([
+                    TextSection::new(
+                        format!(
+                            "{}\n{}",
+                            localization.get("high-score-new"),
+                            localization.get("high-score-name")
+                        ),
+                        stats_style.clone(),
+                    ),
+                    TextSection::new("_", stats_style.clone()),
+                    TextSection::new(
+                        format!("\n{}", localization.get("high-score-save")),
+                        stats_style,
+                    ),
+                ])
+                .with_alignment(TextAlignment::TOP_RIGHT),
+                transform: Transform::from_xyz(WIDTH / 2.0 - 10.0, HEIGHT / 2.0 - 10.0, 1.0),
+                ..default()
+            },
+            NameText,
+            FinishMarker,
+        ));
+    }
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(text, text_style),
+            text_2d_bounds: Text2dBounds { size: box_size },
+
+            transform: Transform::from_xyz(
+                box_position.x - box_size.x / 2.0,
+                box_position.y + box_size.y / 2.0,
+                1.0,
+            ),
+            ..default()
+        },
+        FinishMarker,
+    ));
+}
+
+fn handle_export(
+    keys: Res<Input<KeyCode>>,
+    stats: Res<RunStats>,
+    name_entry: Option<Res<NameEntry>>,
+    mut hint: Query<&mut Text, With<ExportHint>>,
+    localization: Res<Localization>,
+) {
+    // E is just a letter while the name is typed
+    if name_entry.is_none() && keys.just_pressed(KeyCode::E) {
+        let result = export_stats(&stats, &localization);
+        for mut text in hint.iter_mut() {
+            text.sections[1].value = format!("\n\n{result}");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_name_entry(
+    mut commands: Commands,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    name_entry: Option<ResMut<NameEntry>>,
+    stats: Res<RunStats>,
+    mut high_scores: ResMut<HighScores>,
+    mut text: Query<&mut Text, With<NameText>>,
+    localization: Res<Localization>,
+) {
+    let Some(mut name_entry) = name_entry else {return};
+    let mut name = name_entry.0.clone();
+    for c in characters.iter().map(|c| c.char) {
+        if (c.is_ascii_alphanumeric() || c == ' ') && name.len() < MAX_NAME_LENGTH {
+            name.push(c.to_ascii_uppercase());
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name.pop();
+    }
+    if name != name_entry.0 {
+        name_entry.0 = name;
+    }
+
+    let submitted = keys.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter]);
+    if submitted {
+        name_entry.submit(&stats, &mut high_scores);
+        commands.remove_resource::<NameEntry>();
+    }
+    if submitted || name_entry.is_changed() {
+        for mut text in text.iter_mut() {
+            if submitted {
+                text.sections[0].value = localization.get("high-score-saved");
+                text.sections[1].value = name_entry.0.clone();
+                text.sections[2].value = format!("\n{}", localization.get("play-again"));
+            } else {
+                text.sections[1].value = format!("{}_", name_entry.0);
+            }
+        }
+    }
+}
 
-    commands.spawn(Text2dBundle {
-        text: Text::from_section(text, text_style),
-        text_2d_bounds: Text2dBounds { size: box_size },
-
-        transform: Transform::from_xyz(
-            box_position.x - box_size.x / 2.0,
-            box_position.y + box_size.y / 2.0,
-            1.0,
-        ),
-        ..default()
-    });
+fn drop_name_entry(mut commands: Commands) {
+    commands.remove_resource::<NameEntry>();
 }
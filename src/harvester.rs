@@ -1,6 +1,12 @@
 use std::f32::consts::FRAC_PI_2;
 
 use crate::{
+    atlas::Animation,
+    events::GameEvent,
+    i18n::Localization,
+    net::NetRole,
+    picking::Clickable,
+    settings::GameplaySettings,
     terrain::{TerrainMarker, TERRAIN_SIZE},
     tooltip::TooltipString,
     util::{PanelAssetHandlers, TerrainAssetHandlers},
@@ -8,6 +14,7 @@ use crate::{
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{Collider, RigidBody};
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 
 use super::*;
 
@@ -16,8 +23,8 @@ pub const MAX_HELIUM: usize = 30;
 pub const BREAKTIME: (i32, i32) = (300, 2000);
 
 pub fn add_harvester(
-    mut commands: Commands,
-    terrain_assets: Res<TerrainAssetHandlers>,
+    commands: &mut Commands,
+    terrain_assets: &TerrainAssetHandlers,
     cell: (i8, i8),
     slot: usize,
     slot_icon: SlotIcon,
@@ -33,11 +40,7 @@ pub fn add_harvester(
     let mut rng = thread_rng();
 
     let harvester_id = commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(terrain_assets.harvester.1),
-                ..default()
-            },
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3::new(
                     center_coords.0 - (CELL_SIZE_TERRAIN * PIXEL_MULTIPLIER),
@@ -47,9 +50,7 @@ pub fn add_harvester(
                 rotation: Quat::from_rotation_z(-FRAC_PI_2),
                 ..Default::default()
             },
-            texture: terrain_assets.harvester.0.clone(),
-
-            ..default()
+            ..terrain_assets.harvester.sprite_sheet()
         })
         .insert(Harvester)
         .insert(Cell(cell))
@@ -60,38 +61,32 @@ pub fn add_harvester(
             RigidBody::KinematicPositionBased,
             Collider::cuboid(10.0 * PIXEL_MULTIPLIER, 10.0 * PIXEL_MULTIPLIER),
         ))
-        .insert(TooltipString("Working...".to_string()))
+        .insert(Clickable)
+        // Filled in by `update_center`, like the center's below
+        .insert(TooltipString::default())
+        .insert(terrain_assets.drill.clone())
         .id();
 
     let lamp_id = commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(terrain_assets.center.1),
-                ..default()
-            },
-            texture: terrain_assets.center_terrain_lamps[2].0.clone(),
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3::new(center_coords.0, center_coords.1, 1.5),
                 ..Default::default()
             },
-            ..default()
+            ..terrain_assets.center_terrain_lamps[2].sprite_sheet()
         })
         .insert(Lamp)
+        .insert(terrain_assets.lamps[2].clone())
         .insert(TerrainMarker)
         .id();
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(terrain_assets.center.1),
-                ..default()
-            },
-            texture: terrain_assets.center.0.clone(),
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3::new(center_coords.0, center_coords.1, 1.0),
                 ..Default::default()
             },
-            ..default()
+            ..terrain_assets.center.sprite_sheet()
         })
         .insert(Center)
         .insert(BreakTime(rng.gen_range(BREAKTIME.0..BREAKTIME.1)))
@@ -103,7 +98,8 @@ pub fn add_harvester(
         .insert(HarvestTime(0))
         .insert(Helium(0))
         .insert(SlotNumber(slot))
-        .insert(TooltipString("Collecting...".to_string()))
+        .insert(Clickable)
+        .insert(TooltipString::default())
         .insert((
             RigidBody::Fixed,
             Collider::cuboid(11.0 * PIXEL_MULTIPLIER, 11.0 * PIXEL_MULTIPLIER),
@@ -157,7 +153,7 @@ pub fn move_harvesters(
     }
 }
 
-#[allow(clippy::type_complexity)]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn update_center(
     mut centers: Query<
         (
@@ -174,10 +170,18 @@ pub fn update_center(
         ),
         (With<Center>, Without<Harvester>),
     >,
-    mut harvesters: Query<(&mut Moves, &mut TooltipString), (With<Harvester>, Without<Center>)>,
+    mut harvesters: Query<
+        (&mut Moves, &mut TooltipString, &mut Animation),
+        (With<Harvester>, Without<Center>),
+    >,
     terrain_assets: Res<TerrainAssetHandlers>,
     panel_assets: Res<PanelAssetHandlers>,
-    mut imgs: Query<&mut Handle<Image>>,
+    mut sprites: Query<&mut TextureAtlasSprite>,
+    mut animations: Query<&mut Animation, Without<Harvester>>,
+    role: Res<NetRole>,
+    localization: Res<Localization>,
+    gameplay: Res<GameplaySettings>,
+    mut events: EventWriter<GameEvent>,
 ) {
     for (
         harvester_id,
@@ -192,66 +196,92 @@ pub fn update_center(
         mut breaktime,
     ) in centers.iter_mut()
     {
-        let (mut harvester, mut harv_string) = harvesters.get_mut(harvester_id.0).unwrap();
-        let mut lamp = imgs.get_mut(lamp_id.0).unwrap();
-        if helium.0 == MAX_HELIUM {
+        let (mut harvester, mut harv_string, mut drill) =
+            harvesters.get_mut(harvester_id.0).unwrap();
+        // Clients only mirror what the host simulates
+        let simulate = role.is_authoritative();
+        let previous_state = *state;
+        if simulate && helium.0 == MAX_HELIUM {
             *state = HarvesterState::Full;
         }
-        if breaktime.0 <= 0 {
+        if simulate && breaktime.0 <= 0 {
             *state = HarvesterState::Broken;
         }
+        // Clients get the events of the host with its snapshots
+        if simulate && *state != previous_state {
+            match *state {
+                HarvesterState::Full => events.send(GameEvent::HarvesterFull { slot: slot.0 }),
+                HarvesterState::Broken => events.send(GameEvent::HarvesterBroke { slot: slot.0 }),
+                HarvesterState::Work => {}
+            }
+        }
         match *state {
             HarvesterState::Work => {
-                time.0 += 1;
-                if time.0 >= HARVEST_SPEED {
-                    helium.0 += 1;
-                    time.0 = 0;
+                if simulate {
+                    time.0 += 1;
+                    if time.0 >= HARVEST_SPEED {
+                        helium.0 += 1;
+                        time.0 = 0;
+                        events.send(GameEvent::HeliumHarvested { slot: slot.0 });
+                    }
+                    breaktime.0 -= 1;
                 }
-                breaktime.0 -= 1;
-                string.0 = format!(
-                    "Harvester {}\nStatus: Working\nHelium amount: {}/{}",
-                    slot.0, helium.0, MAX_HELIUM
-                );
                 harvester.0 = true;
-                harv_string.0 = "Collecting...".to_string();
-                *lamp = terrain_assets.center_terrain_lamps[2].0.clone();
-            }
-            HarvesterState::Full => {
-                string.0 = format!(
-                    "Harvester {}\nStatus: Full\nClick to collect helium",
-                    slot.0
-                );
-                harvester.0 = false;
-                harv_string.0 = "Waiting...".to_string();
-                *lamp = terrain_assets.center_terrain_lamps[1].0.clone();
-            }
-            HarvesterState::Broken => {
-                string.0 = format!("Harvester {}\nStatus: Broken\nClick to repair", slot.0);
-                harvester.0 = false;
-                harv_string.0 = "Waiting...".to_string();
-                *lamp = terrain_assets.center_terrain_lamps[0].0.clone();
             }
+            HarvesterState::Full | HarvesterState::Broken => harvester.0 = false,
+        }
+        let (status, hint) = match *state {
+            HarvesterState::Work => (
+                "harvester-working",
+                localization.format(
+                    "harvester-helium",
+                    &[("helium", helium.0.into()), ("max", MAX_HELIUM.into())],
+                ),
+            ),
+            HarvesterState::Full => ("harvester-full", localization.get("harvester-collect")),
+            HarvesterState::Broken => ("harvester-broken", localization.get("harvester-repair")),
+        };
+        // Counted from 1 like the slots on the panel and in the event log
+        *string = TooltipString::text(
+            localization.format("harvester-title", &[("slot", (slot.0 + 1).into())]) + "\n",
+        )
+        .with(
+            localization.get(status) + "\n",
+            gameplay.palette.color(*state),
+        )
+        .with(hint, Color::WHITE)
+        .with_icon(&panel_assets.harv_icon, Color::WHITE);
+        *harv_string = TooltipString::text(localization.get(if harvester.0 {
+            "harvester-collecting"
+        } else {
+            "harvester-waiting"
+        }))
+        .with_icon(&panel_assets.harv_icon, Color::WHITE);
+        // The drill turns while it works
+        drill.speed = if harvester.0 { 1.0 } else { 0.0 };
+        let (lamp_color, icon_color) = match *state {
+            HarvesterState::Work => (2, 0),
+            HarvesterState::Full => (1, 1),
+            HarvesterState::Broken => (0, 2),
+        };
+        if let Ok(mut lamp) = animations.get_mut(lamp_id.0) {
+            // Blinks while it's broken, otherwise it switches on once
+            lamp.switch_to(&terrain_assets.lamps[lamp_color]);
+        }
+        if let Ok(mut icon) = animations.get_mut(center_icon.0) {
+            icon.switch_to(&panel_assets.center_icon_frames[icon_color]);
         }
-        let mut slot_img = imgs.get_mut(slot_icon.0).unwrap();
+
+        let mut slot_img = sprites.get_mut(slot_icon.0).unwrap();
 
         let new_slot_img_idx = match *state {
             HarvesterState::Work => 1,
             HarvesterState::Full => 2,
             HarvesterState::Broken => 3,
         };
-        *slot_img = panel_assets.harv_slots[slot.0.min(panel_assets.harv_slots.len() - 1)]
+        slot_img.index = panel_assets.harv_slots[slot.0.min(panel_assets.harv_slots.len() - 1)]
             [new_slot_img_idx]
-            .0
-            .clone();
-
-        let new_center_img_idx = match *state {
-            HarvesterState::Work => 0,
-            HarvesterState::Full => 1,
-            HarvesterState::Broken => 2,
-        };
-
-        *imgs.get_mut(center_icon.0).unwrap() =
-            panel_assets.center_icon[new_center_img_idx].0.clone();
+            .index;
     }
 }
 
@@ -279,7 +309,7 @@ pub struct Center;
 pub struct SlotNumber(pub usize);
 
 #[derive(Component)]
-pub struct HarvesterId(Entity);
+pub struct HarvesterId(pub Entity);
 
 #[derive(Component)]
 pub struct SlotIcon(pub Entity);
@@ -288,7 +318,7 @@ pub struct SlotIcon(pub Entity);
 pub struct CenterIcon(pub Entity);
 
 #[derive(Component)]
-pub struct LampId(Entity);
+pub struct LampId(pub Entity);
 
 #[derive(Component)]
 pub struct Helium(pub usize);
@@ -299,7 +329,7 @@ pub struct BreakTime(pub i32);
 #[derive(Component)]
 pub struct HarvestTime(usize);
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HarvesterState {
     Work,
     Full,
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::Localization, persist, stats::RunStats};
+
+use super::*;
+
+/// How many runs the table remembers
+const TABLE_SIZE: usize = 10;
+const SAVE_KEY: &str = "high_scores";
+pub const MAX_NAME_LENGTH: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub canisters: usize,
+    pub won: bool,
+}
+
+/// Best runs first
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct HighScores(pub Vec<HighScore>);
+
+impl HighScores {
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.0.len() < TABLE_SIZE || self.0.iter().any(|s| s.score < score))
+    }
+
+    pub fn insert(&mut self, entry: HighScore) {
+        let position = self.0.partition_point(|s| s.score >= entry.score);
+        self.0.insert(position, entry);
+        self.0.truncate(TABLE_SIZE);
+    }
+
+    pub fn table(&self, localization: &Localization) -> String {
+        let mut table = localization.get("high-scores-title") + "\n";
+        if self.0.is_empty() {
+            table += "\n";
+            table += &localization.get("high-scores-empty");
+        }
+        for (place, entry) in self.0.iter().enumerate() {
+            let mark = if entry.won { '*' } else { ' ' };
+            table += &format!(
+                "\n{:>2}. {:<width$} {:>5}{mark}",
+                place + 1,
+                entry.name,
+                entry.score,
+                width = MAX_NAME_LENGTH,
+            );
+        }
+        table
+    }
+}
+
+/// Present while the player types a name for a run that made it into the table
+#[derive(Resource, Default)]
+pub struct NameEntry(pub String);
+
+impl NameEntry {
+    pub fn submit(&self, stats: &RunStats, high_scores: &mut HighScores) {
+        let name = match self.0.trim() {
+            "" => "ANON".to_string(),
+            name => name.to_string(),
+        };
+        high_scores.insert(HighScore {
+            name,
+            score: stats.score,
+            canisters: stats.canisters,
+            won: stats.won,
+        });
+        persist::save(SAVE_KEY, &*high_scores);
+    }
+}
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(persist::load::<HighScores>(SAVE_KEY));
+    }
+}
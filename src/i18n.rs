@@ -0,0 +1,253 @@
+//! Translations of every text in the game, kept in Fluent files under `assets/locales`.
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{settings::GameplaySettings, tooltip::TooltipString};
+
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Russian,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Russian];
+
+    /// Shown in the language picker, so everyone can find their own
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Russian => "Русский",
+        }
+    }
+
+    /// Picks the plural rules
+    fn id(self) -> LanguageIdentifier {
+        let id = match self {
+            Language::English => "en",
+            Language::Russian => "ru",
+        };
+        id.parse().expect("valid language identifier")
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../assets/locales/en.ftl"),
+            Language::Russian => include_str!("../assets/locales/ru.ftl"),
+        }
+    }
+
+    /// Messages of the language, broken entries are skipped with a warning
+    fn bundle(self) -> FluentBundle<FluentResource> {
+        let resource = FluentResource::try_new(self.source().to_string()).unwrap_or_else(
+            |(resource, errors)| {
+                for e in errors {
+                    warn!("Can't parse {}: {e:?}", self.id());
+                }
+                resource
+            },
+        );
+        let mut bundle = FluentBundle::new_concurrent(vec![self.id()]);
+        // The font has no glyphs for the bidi isolation marks around placeables
+        bundle.set_use_isolating(false);
+        if let Err(errors) = bundle.add_resource(resource) {
+            for e in errors {
+                warn!("Can't add a message to {}: {e:?}", self.id());
+            }
+        }
+        bundle
+    }
+}
+
+/// Value of a `$variable` in a message
+pub enum Arg {
+    Number(f64),
+    Text(String),
+}
+
+macro_rules! number_arg {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Arg {
+            fn from(n: $t) -> Self {
+                Arg::Number(n as f64)
+            }
+        })*
+    };
+}
+
+number_arg!(usize, u32, i32, f32);
+
+impl From<&str> for Arg {
+    fn from(s: &str) -> Self {
+        Arg::Text(s.to_string())
+    }
+}
+
+impl From<String> for Arg {
+    fn from(s: String) -> Self {
+        Arg::Text(s)
+    }
+}
+
+/// Messages of the picked language, English fills in whatever it lacks
+#[derive(Resource)]
+pub struct Localization {
+    language: Language,
+    messages: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+impl Localization {
+    pub fn new(language: Language) -> Self {
+        Localization {
+            language,
+            messages: language.bundle(),
+            fallback: Language::English.bundle(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> String {
+        self.format(id, &[])
+    }
+
+    pub fn format(&self, id: &str, args: &[(&str, Arg)]) -> String {
+        let found = [&self.messages, &self.fallback]
+            .into_iter()
+            .find_map(|bundle| Some((bundle, bundle.get_message(id)?.value()?)));
+        let Some((bundle, pattern)) = found else {
+            warn!("Missing message {id}");
+            return id.to_string();
+        };
+        let mut fluent_args = FluentArgs::new();
+        for (name, arg) in args {
+            match arg {
+                Arg::Number(n) => fluent_args.set(*name, FluentValue::from(*n)),
+                Arg::Text(text) => fluent_args.set(*name, FluentValue::from(text.as_str())),
+            }
+        }
+        let mut errors = vec![];
+        let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        for e in errors {
+            warn!("Can't format message {id}: {e:?}");
+        }
+        text.into_owned()
+    }
+}
+
+/// Text or tooltip that is just one message, it follows the language picked in the settings
+#[derive(Component)]
+pub struct Localized(pub &'static str);
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        let language = app
+            .world
+            .get_resource::<GameplaySettings>()
+            .map(|settings| settings.language)
+            .unwrap_or_default();
+        app.insert_resource(Localization::new(language))
+            .add_system(switch_language)
+            .add_system(relabel.after(switch_language));
+    }
+}
+
+fn switch_language(settings: Res<GameplaySettings>, mut localization: ResMut<Localization>) {
+    if settings.is_changed() && settings.language != localization.language {
+        *localization = Localization::new(settings.language);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn relabel(
+    localization: Res<Localization>,
+    mut texts: Query<(&Localized, ChangeTrackers<Localized>, &mut Text)>,
+    mut tooltips: Query<(&Localized, ChangeTrackers<Localized>, &mut TooltipString), Without<Text>>,
+) {
+    for (localized, tracker, mut text) in texts.iter_mut() {
+        if localization.is_changed() || tracker.is_added() {
+            text.sections[0].value = localization.get(localized.0);
+        }
+    }
+    for (localized, tracker, mut tooltip) in tooltips.iter_mut() {
+        if localization.is_changed() || tracker.is_added() {
+            *tooltip = TooltipString::text(localization.get(localized.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(language: Language, source: &str, args: &[(&str, Arg)]) -> String {
+        let resource = FluentResource::try_new(source.to_string()).expect("valid Fluent");
+        let mut bundle = FluentBundle::new_concurrent(vec![language.id()]);
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).expect("unique ids");
+        let localization = Localization {
+            language,
+            messages: bundle,
+            fallback: FluentBundle::new_concurrent(vec![language.id()]),
+        };
+        localization.format("test", args)
+    }
+
+    #[test]
+    fn multiline_message() {
+        let source = "test =\n    First line\n      indented\n\n    after a blank\nnext = Next";
+        assert_eq!(
+            format(Language::English, source, &[]),
+            "First line\n  indented\n\nafter a blank"
+        );
+    }
+
+    #[test]
+    fn select_falls_back_to_default() {
+        let source = "test = { $kind ->\n    [buggy] Buggy\n   *[other] Something\n}";
+        let kind = |kind: &str| format(Language::English, source, &[("kind", kind.into())]);
+        assert_eq!(kind("buggy"), "Buggy");
+        assert_eq!(kind("rock"), "Something");
+    }
+
+    #[test]
+    fn exact_number_before_category() {
+        let source = "test = { $n ->\n    [0] None left\n    [one] { $n } left\n   *[other] { $n } left in total\n}";
+        let left = |n: usize| format(Language::English, source, &[("n", n.into())]);
+        assert_eq!(left(0), "None left");
+        assert_eq!(left(1), "1 left");
+        assert_eq!(left(5), "5 left in total");
+    }
+
+    #[test]
+    fn russian_plural_categories() {
+        let source = "test = { $n ->\n    [one] one\n    [few] few\n   *[many] many\n}";
+        let categories = [1, 2, 5, 11, 12, 21, 22, 25]
+            .map(|n: usize| format(Language::Russian, source, &[("n", n.into())]));
+        assert_eq!(
+            categories,
+            ["one", "few", "many", "many", "many", "one", "few", "many"]
+        );
+    }
+
+    #[test]
+    fn terms_attributes_and_references() {
+        let source = "-ship = Ship\nother = { -ship } waits\n    .hint = Hint\ntest = { other }, { other.hint }";
+        assert_eq!(format(Language::English, source, &[]), "Ship waits, Hint");
+    }
+
+    #[test]
+    fn every_message_parses() {
+        for language in Language::ALL {
+            if let Err((_, errors)) = FluentResource::try_new(language.source().to_string()) {
+                panic!("{} doesn't parse: {errors:?}", language.id());
+            }
+        }
+    }
+}
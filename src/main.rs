@@ -1,19 +1,40 @@
-use std::io::Cursor;
-
-use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioControl, AudioPlugin, AudioSource};
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use bevy_kira_audio::{AudioPlugin, AudioSource};
 use harvester::update_center;
-use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
-use tooltip::update_tooltip;
 use util::load_assets;
 
+mod achievements;
+#[cfg(feature = "hot_reload")]
+mod aseprite;
+mod atlas;
 mod buggy;
+#[cfg(not(target_arch = "wasm32"))]
+mod check;
+mod cutscene;
+mod events;
+mod feed;
 mod finish;
 mod harvester;
+mod highscore;
+mod i18n;
+mod menu;
+mod music;
+mod net;
+mod palette;
 mod panel;
+mod pause;
+mod persist;
+mod picking;
+mod scaling;
+mod settings;
+mod sfx;
 mod start;
+mod stats;
+mod synth;
 mod terrain;
+mod toast;
 mod tooltip;
+mod tutorial;
 mod util;
 
 pub const PIXEL_MULTIPLIER: f32 = 5.0;
@@ -22,31 +43,68 @@ pub const WIDTH: f32 = 160.0 * PIXEL_MULTIPLIER;
 pub const HEIGHT: f32 = 120.0 * PIXEL_MULTIPLIER;
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if std::env::args().any(|arg| arg == "--check-assets") {
+        std::process::exit(check::check_assets());
+    }
+
     let start = || {
         App::new()
             .add_state(AppState::Start)
             .insert_resource(ClearColor(Color::BLACK))
-            .add_plugins(DefaultPlugins.set(WindowPlugin {
-                window: WindowDescriptor {
-                    title: "Moon 2023".to_string(),
-                    width: WIDTH,
-                    height: HEIGHT,
-                    ..default()
-                },
-                ..default()
-            }))
+            .add_plugins(
+                DefaultPlugins
+                    .set(WindowPlugin {
+                        window: WindowDescriptor {
+                            title: "Moon 2023".to_string(),
+                            width: WIDTH,
+                            height: HEIGHT,
+                            resize_constraints: bevy::window::WindowResizeConstraints {
+                                min_width: scaling::NATIVE_SIZE.x as f32,
+                                min_height: scaling::NATIVE_SIZE.y as f32,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    // `cargo run --features hot_reload` repaints the art as soon as it's saved
+                    .set(AssetPlugin {
+                        watch_for_changes: cfg!(feature = "hot_reload"),
+                        ..default()
+                    }),
+            )
+            .add_plugin(atlas::AtlasPlugin)
+            .add_plugin(scaling::ScalingPlugin)
+            .add_plugin(picking::PickingPlugin)
+            .add_plugin(tooltip::TooltipPlugin)
+            .add_plugin(palette::PalettePlugin)
             .add_plugin(start::StartPlugin)
             .add_plugin(terrain::TerrainPlugin)
             .add_plugin(panel::PanelPlugin)
+            .add_plugin(cutscene::CutscenePlugin)
             .add_plugin(finish::Finish)
+            .add_plugin(net::NetPlugin)
+            .add_plugin(stats::StatsPlugin)
+            .add_plugin(highscore::HighScorePlugin)
+            .add_plugin(toast::ToastPlugin)
+            .add_plugin(achievements::AchievementsPlugin)
+            .add_plugin(feed::EventFeedPlugin)
             .add_plugin(AudioPlugin)
+            .add_plugin(sfx::SfxPlugin)
+            .add_plugin(music::MusicPlugin)
+            .add_plugin(settings::SettingsPlugin)
+            .add_plugin(i18n::LocalizationPlugin)
+            .add_plugin(pause::PausePlugin)
+            .add_plugin(tutorial::TutorialPlugin)
             // .add_startup_system(spawn_tooltip)
             .add_system(handle_input)
-            .add_system(update_tooltip)
-            .add_system(update_center)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .with_system(update_center),
+            )
             .add_startup_system(load_assets)
-            .add_startup_system(music)
-            .insert_resource(MusicStarted(false))
             .run()
     };
 
@@ -77,31 +135,50 @@ fn main() {
     }
 }
 
-fn handle_input(keys: Res<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+fn handle_input(
+    keys: Res<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    role: Res<net::NetRole>,
+    name_entry: Option<Res<highscore::NameEntry>>,
+) {
     if keys.just_pressed(KeyCode::Space) {
         let state = app_state.current().clone();
+        // Space is part of the name while a high score is being entered
+        if state == AppState::Finish && name_entry.is_some() {
+            return;
+        }
+        // Clients start together with the host
+        if state == AppState::Start && !role.is_authoritative() {
+            return;
+        }
         app_state
             .set(match state {
                 AppState::Start => AppState::Terrain,
                 AppState::Terrain => AppState::Panel,
                 AppState::Panel => AppState::Terrain,
+                // Skipping is up to the cutscene itself
+                AppState::Cutscene => return,
+                AppState::Settings | AppState::Paused => return,
                 AppState::Finish => AppState::Start,
             })
             .unwrap()
     }
 }
 
-#[derive(Resource)]
-struct MusicStarted(bool);
+/// The screen underneath the pause menu and the settings, which are pushed on top of it
+pub fn screen(state: &State<AppState>) -> &AppState {
+    std::iter::once(state.current())
+        .chain(state.inactives().iter().rev())
+        .find(|s| !matches!(s, AppState::Paused | AppState::Settings))
+        .unwrap_or(&AppState::Start)
+}
 
-fn music(audio: Res<Audio>, mut source: ResMut<Assets<AudioSource>>) {
-    let data = StaticSoundData::from_cursor(
-        Cursor::new(include_bytes!("../assets/theme.ogg")),
-        StaticSoundSettings::default(),
-    )
-    .unwrap();
-    let handle = source.add(AudioSource { sound: data });
-    audio.play(handle).with_volume(0.2).looped();
+/// Run criteria for gameplay systems that must keep going on both the terrain and the panel
+pub fn in_game(state: Res<State<AppState>>) -> ShouldRun {
+    match state.current() {
+        AppState::Terrain | AppState::Panel => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -109,5 +186,11 @@ pub enum AppState {
     Start,
     Terrain,
     Panel,
+    /// The ship arrives and either takes the player with the canisters or flies by
+    Cutscene,
     Finish,
+    /// Pushed on top of the screen it was opened from
+    Settings,
+    /// Pushed on top of the terrain or the panel, everything in the run stands still
+    Paused,
 }
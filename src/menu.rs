@@ -0,0 +1,109 @@
+//! Keyboard driven full-screen menus drawn as UI over whatever screen they were opened from
+
+use crate::i18n::Localized;
+
+use super::*;
+
+/// Font, title and hint line of a menu given as message ids, rows are spawned by the caller
+pub fn spawn_menu(
+    commands: &mut Commands,
+    fonts: &mut Assets<Font>,
+    marker: impl Component,
+    title: &'static str,
+    hint: &'static str,
+    rows: impl FnOnce(&mut ChildBuilder, &TextStyle),
+) {
+    let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
+    // FIXME (samoylovfp) deduplicate
+    let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
+    let text_style = TextStyle {
+        font: font_handle,
+        font_size: 16.0,
+        color: Color::GRAY,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.9).into(),
+                z_index: ZIndex::Global(5),
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|menu| {
+            menu.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                        ..text_style.clone()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+                Localized(title),
+            ));
+            rows(menu, &text_style);
+            menu.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 10.0,
+                        ..text_style.clone()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+                Localized(hint),
+            ));
+        });
+}
+
+pub fn row_bundle(label: String, text_style: &TextStyle) -> TextBundle {
+    TextBundle::from_section(label, text_style.clone()).with_style(Style {
+        margin: UiRect::all(Val::Px(6.0)),
+        ..default()
+    })
+}
+
+pub fn row_color(selected: bool) -> Color {
+    if selected {
+        Color::YELLOW
+    } else {
+        Color::GRAY
+    }
+}
+
+/// Up and Down move through `len` rows, wrapping around
+pub fn move_selection(keys: &Input<KeyCode>, selected: &mut usize, len: usize) {
+    if keys.just_pressed(KeyCode::Up) {
+        *selected = (*selected + len - 1) % len;
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        *selected = (*selected + 1) % len;
+    }
+}
+
+/// Whether any of the keys was just pressed, and if so hides it from the rest of the frame,
+/// otherwise the menu underneath reacts to the same press once this one closes
+pub fn take_keys(keys: &mut Input<KeyCode>, taken: impl IntoIterator<Item = KeyCode>) -> bool {
+    taken
+        .into_iter()
+        .filter(|&key| keys.clear_just_pressed(key))
+        .count()
+        > 0
+}
@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use bevy_kira_audio::{Audio, AudioControl, AudioInstance, AudioTween};
+
+use crate::{
+    harvester::{Center, HarvesterState, StoredCanisters},
+    settings::AudioSettings,
+    start::EndTimer,
+    terrain::CANISTERS_TO_WIN,
+    util::{load_music, MusicAssetHandlers},
+};
+
+use super::*;
+
+/// Seconds left on the clock when the pressure layer starts fading in
+const PRESSURE_SECS: f32 = 30.0;
+/// Broken harvesters it takes for the trouble layer to play at full volume
+const TROUBLE_AT_FULL: usize = 3;
+const CROSSFADE: Duration = Duration::from_millis(1500);
+/// Smaller level changes wait, so the tweens aren't restarted every frame
+const LEVEL_THRESHOLD: f32 = 0.05;
+
+/// Looping layers, all of them play all the time and are mixed by volume
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Title,
+    Theme,
+    Pressure,
+    Trouble,
+    Outro,
+}
+
+const LAYERS: [Layer; 5] = [
+    Layer::Title,
+    Layer::Theme,
+    Layer::Pressure,
+    Layer::Trouble,
+    Layer::Outro,
+];
+
+#[derive(Resource)]
+struct Music {
+    instances: [Handle<AudioInstance>; LAYERS.len()],
+    /// Last volume set on every layer, before the audio settings are applied
+    levels: [f32; LAYERS.len()],
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_music)
+            .add_startup_system_to_stage(StartupStage::PostStartup, start_music)
+            .add_system_set(SystemSet::on_enter(AppState::Cutscene).with_system(play_jingle))
+            .add_system(mix_music);
+    }
+}
+
+fn start_music(mut commands: Commands, audio: Res<Audio>, tracks: Res<MusicAssetHandlers>) {
+    let play =
+        |track: &Handle<AudioSource>| audio.play(track.clone()).with_volume(0.0).looped().handle();
+    commands.insert_resource(Music {
+        instances: LAYERS.map(|layer| match layer {
+            Layer::Title => play(&tracks.title),
+            Layer::Theme => play(&tracks.theme),
+            Layer::Pressure => play(&tracks.pressure),
+            Layer::Trouble => play(&tracks.trouble),
+            Layer::Outro => play(&tracks.outro),
+        }),
+        levels: [0.0; LAYERS.len()],
+    });
+}
+
+fn play_jingle(
+    audio: Res<Audio>,
+    tracks: Res<MusicAssetHandlers>,
+    settings: Res<AudioSettings>,
+    canisters: Res<StoredCanisters>,
+) {
+    let jingle = if canisters.0 >= CANISTERS_TO_WIN {
+        &tracks.win
+    } else {
+        &tracks.lose
+    };
+    audio
+        .play(jingle.clone())
+        .with_volume(settings.music_volume());
+}
+
+fn mix_music(
+    mut music: ResMut<Music>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    settings: Res<AudioSettings>,
+    state: Res<State<AppState>>,
+    timer: Query<&EndTimer>,
+    centers: Query<&HarvesterState, With<Center>>,
+) {
+    // Menus on top keep the music of the screen underneath
+    let screen = screen(&state);
+    let in_game = matches!(screen, AppState::Terrain | AppState::Panel);
+    let pressure = match timer.get_single() {
+        Ok(timer) if in_game => (1.0 - timer.timer.remaining_secs() / PRESSURE_SECS).max(0.0),
+        _ => 0.0,
+    };
+    let broken = centers
+        .iter()
+        .filter(|s| **s == HarvesterState::Broken)
+        .count();
+    let trouble = match in_game {
+        true => (broken as f32 / TROUBLE_AT_FULL as f32).min(1.0),
+        false => 0.0,
+    };
+
+    for (i, layer) in LAYERS.into_iter().enumerate() {
+        let level = match layer {
+            Layer::Title => on_off(*screen == AppState::Start),
+            Layer::Theme => on_off(in_game),
+            Layer::Pressure => pressure,
+            Layer::Trouble => trouble,
+            Layer::Outro => on_off(*screen == AppState::Finish),
+        };
+        let old = music.levels[i];
+        let settled =
+            level == old || ((level - old).abs() < LEVEL_THRESHOLD && level != 0.0 && level != 1.0);
+        if settled && !settings.is_changed() {
+            continue;
+        }
+        // The instance only exists once the audio thread started playing it
+        let Some(instance) = instances.get_mut(&music.instances[i]) else {continue};
+        instance.set_volume(
+            level as f64 * settings.music_volume(),
+            AudioTween::linear(CROSSFADE),
+        );
+        music.levels[i] = level;
+    }
+}
+
+fn on_off(on: bool) -> f32 {
+    if on {
+        1.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,541 @@
+//! Online co-op through a WebSocket relay (see `src/bin/relay.rs`).
+//!
+//! One game is started with `--host ws://relay:port` and runs the simulation,
+//! the others are started with `--join ws://relay:port`, send their input and
+//! actions to the host and mirror the snapshots it broadcasts.
+
+use std::{
+    f32::consts::{PI, TAU},
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use bevy::{ecs::schedule::ShouldRun, utils::HashMap};
+use bevy_rapier2d::prelude::{RigidBody, Velocity};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buggy::{spawn_buggy, Buggy, BuggyInput, LocalControl, LocalPlayers},
+    events::GameEvent,
+    harvester::{
+        Cell, Center, HarvesterId, HarvesterState, Helium, SlotNumber, StorageHelium,
+        StoredCanisters, TotalHarvesters,
+    },
+    i18n::Localization,
+    in_game,
+    panel::{place_harvester, spawn_canister, SlotSprites},
+    start::EndTimer,
+    terrain::{PlayerAction, TerrainSprite, CANISTERS_TO_WIN},
+    toast::Toast,
+    util::{PanelAssetHandlers, TerrainAssetHandlers},
+};
+
+use super::*;
+
+pub const DEFAULT_RELAY: &str = "ws://127.0.0.1:9001";
+const SNAPSHOT_INTERVAL: f32 = 0.05;
+const JOIN_RETRY_INTERVAL: f32 = 1.0;
+
+#[derive(Resource, PartialEq, Eq)]
+pub enum NetRole {
+    Offline,
+    Host,
+    /// `player` is known once the host has welcomed us
+    Client {
+        player: Option<usize>,
+    },
+}
+
+impl NetRole {
+    /// Whether this game runs the simulation or mirrors someone else's
+    pub fn is_authoritative(&self) -> bool {
+        !matches!(self, NetRole::Client { .. })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum NetMessage {
+    Join { nonce: u64 },
+    Welcome { nonce: u64, player: usize },
+    Input { player: usize, input: BuggyInput },
+    Action(PlayerAction),
+    Snapshot(Snapshot),
+}
+
+#[derive(Serialize, Deserialize, Resource, Default)]
+struct Snapshot {
+    in_game: bool,
+    /// The host has its pause menu open, so nothing moves
+    paused: bool,
+    elapsed_secs: f32,
+    storage: usize,
+    canisters: usize,
+    buggies: Vec<BuggySnapshot>,
+    centers: Vec<CenterSnapshot>,
+    /// Everything that happened on the host since the last snapshot
+    events: Vec<GameEvent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BuggySnapshot {
+    player: usize,
+    translation: [f32; 3],
+    rotation: f32,
+    helium: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CenterSnapshot {
+    slot: usize,
+    cell: (i8, i8),
+    state: HarvesterState,
+    helium: usize,
+}
+
+#[derive(Resource)]
+struct NetLink {
+    outgoing: Sender<String>,
+    incoming: Mutex<Receiver<String>>,
+}
+
+impl NetLink {
+    fn send(&self, message: &NetMessage) {
+        let text = serde_json::to_string(message).expect("serializable message");
+        // The connection thread only goes away together with the app
+        let _ = self.outgoing.send(text);
+    }
+
+    fn receive(&self) -> Vec<NetMessage> {
+        let incoming = self.incoming.lock().unwrap();
+        incoming
+            .try_iter()
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect()
+    }
+}
+
+/// Buggy moved by the snapshots, its physics runs on the host
+#[derive(Component)]
+pub struct Mirrored;
+
+/// Players connected from other machines, by the nonce they joined with
+#[derive(Resource, Default)]
+struct RemotePlayers(HashMap<u64, usize>);
+
+#[derive(Resource)]
+struct JoinNonce(u64);
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let (role, url) = role_from_args();
+        let Some(url) = url else {
+            app.insert_resource(NetRole::Offline);
+            return;
+        };
+        app.insert_resource(connect(url));
+        match role {
+            NetRole::Host => {
+                app.insert_resource(RemotePlayers::default())
+                    .add_system(host_receive)
+                    .add_system(host_send_snapshot)
+                    .add_system_set(
+                        SystemSet::new()
+                            .with_run_criteria(in_game)
+                            .with_system(host_spawn_remote_buggies),
+                    );
+            }
+            _ => {
+                app.insert_resource(JoinNonce(random()))
+                    .insert_resource(Snapshot::default())
+                    .add_system(client_join)
+                    .add_system(client_receive)
+                    .add_system(client_show_pause.after(client_receive))
+                    .add_system_set(
+                        SystemSet::new()
+                            .with_run_criteria(snapshot_ready)
+                            .with_system(client_apply_snapshot),
+                    )
+                    .add_system_set(
+                        SystemSet::new()
+                            .with_run_criteria(in_game)
+                            .with_system(client_send_input),
+                    );
+            }
+        }
+        app.insert_resource(role);
+    }
+}
+
+fn role_from_args() -> (NetRole, Option<String>) {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let role = match arg.as_str() {
+            "--host" => NetRole::Host,
+            "--join" => NetRole::Client { player: None },
+            _ => continue,
+        };
+        let url = args.next().unwrap_or_else(|| DEFAULT_RELAY.to_string());
+        return (role, Some(url));
+    }
+    (NetRole::Offline, None)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn connect(url: String) -> NetLink {
+    use std::{net::TcpStream, sync::mpsc::channel, thread};
+    use tungstenite::{stream::MaybeTlsStream, Message};
+
+    let (outgoing, to_socket) = channel::<String>();
+    let (from_socket, incoming) = channel();
+
+    thread::spawn(move || loop {
+        let mut socket = match tungstenite::connect(url.as_str()) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                warn!("Can't reach the relay at {url}: {e}");
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        info!("Connected to the relay at {url}");
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            TcpStream::set_read_timeout(stream, Some(Duration::from_millis(5)))
+                .expect("non-zero timeout");
+        }
+        // Whatever piled up while disconnected is stale by now
+        to_socket.try_iter().for_each(drop);
+
+        loop {
+            let mut alive = to_socket
+                .try_iter()
+                .all(|text| socket.write_message(Message::Text(text)).is_ok());
+            match socket.read_message() {
+                Ok(Message::Text(text)) => {
+                    if from_socket.send(text).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(_) => alive = false,
+            }
+            if !alive {
+                warn!("Lost connection to the relay");
+                break;
+            }
+        }
+    });
+
+    NetLink {
+        outgoing,
+        incoming: Mutex::new(incoming),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn connect(_url: String) -> NetLink {
+    // Browsers need web-sys sockets, for now online play is native only
+    let (outgoing, _) = std::sync::mpsc::channel();
+    let (_, incoming) = std::sync::mpsc::channel();
+    NetLink {
+        outgoing,
+        incoming: Mutex::new(incoming),
+    }
+}
+
+fn host_receive(
+    link: Res<NetLink>,
+    mut remote_players: ResMut<RemotePlayers>,
+    local_players: Res<LocalPlayers>,
+    mut buggies: Query<(&Buggy, &mut BuggyInput), Without<LocalControl>>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    for message in link.receive() {
+        match message {
+            NetMessage::Join { nonce } => {
+                let next_player = local_players.0 + remote_players.0.len();
+                let player = *remote_players.0.entry(nonce).or_insert(next_player);
+                link.send(&NetMessage::Welcome { nonce, player });
+            }
+            NetMessage::Input { player, input } => {
+                if let Some((_, mut buggy_input)) =
+                    buggies.iter_mut().find(|(b, _)| b.player == player)
+                {
+                    *buggy_input = input;
+                }
+            }
+            NetMessage::Action(action) => actions.send(action),
+            _ => {}
+        }
+    }
+}
+
+fn host_spawn_remote_buggies(
+    mut commands: Commands,
+    remote_players: Res<RemotePlayers>,
+    buggies: Query<&Buggy>,
+    terrain_assets: Res<TerrainAssetHandlers>,
+) {
+    for &player in remote_players.0.values() {
+        if !buggies.iter().any(|b| b.player == player) {
+            spawn_buggy(&mut commands, &terrain_assets, player);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn host_send_snapshot(
+    link: Res<NetLink>,
+    time: Res<Time>,
+    mut since_last: Local<f32>,
+    state: Res<State<AppState>>,
+    buggies: Query<(&Buggy, &Transform, &Helium)>,
+    centers: Query<(&SlotNumber, &HarvesterId, &HarvesterState, &Helium), With<Center>>,
+    cells: Query<&Cell>,
+    timer: Query<&EndTimer>,
+    storage: Option<Res<StorageHelium>>,
+    canisters: Option<Res<StoredCanisters>>,
+    mut events: EventReader<GameEvent>,
+    mut pending: Local<Vec<GameEvent>>,
+) {
+    // Read every frame, they'd be gone by the next snapshot
+    pending.extend(events.iter().copied());
+    *since_last += time.delta_seconds();
+    if *since_last < SNAPSHOT_INTERVAL {
+        return;
+    }
+    *since_last = 0.0;
+
+    // A paused host still has a game going
+    let in_game = matches!(screen(&state), AppState::Terrain | AppState::Panel);
+    let mut snapshot = Snapshot {
+        in_game,
+        paused: in_game && state.current() != screen(&state),
+        events: std::mem::take(&mut *pending),
+        ..default()
+    };
+    if in_game {
+        snapshot.elapsed_secs = timer.single().timer.elapsed_secs();
+        snapshot.storage = storage.map_or(0, |s| s.0);
+        snapshot.canisters = canisters.map_or(0, |c| c.0);
+        snapshot.buggies = buggies
+            .iter()
+            .map(|(buggy, transform, helium)| BuggySnapshot {
+                player: buggy.player,
+                translation: transform.translation.to_array(),
+                rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+                helium: helium.0,
+            })
+            .collect();
+        snapshot.centers = centers
+            .iter()
+            .map(|(slot, harvester, state, helium)| CenterSnapshot {
+                slot: slot.0,
+                cell: cells.get(harvester.0).map_or((0, 0), |c| c.0),
+                state: *state,
+                helium: helium.0,
+            })
+            .collect();
+        snapshot.centers.sort_by_key(|c| c.slot);
+    }
+    link.send(&NetMessage::Snapshot(snapshot));
+}
+
+fn client_join(
+    link: Res<NetLink>,
+    role: Res<NetRole>,
+    nonce: Res<JoinNonce>,
+    time: Res<Time>,
+    mut since_last: Local<Option<f32>>,
+) {
+    if *role != (NetRole::Client { player: None }) {
+        return;
+    }
+    // The host may connect after us, so keep asking
+    let since = since_last.get_or_insert(JOIN_RETRY_INTERVAL);
+    *since += time.delta_seconds();
+    if *since >= JOIN_RETRY_INTERVAL {
+        *since = 0.0;
+        link.send(&NetMessage::Join { nonce: nonce.0 });
+    }
+}
+
+fn client_receive(
+    link: Res<NetLink>,
+    mut role: ResMut<NetRole>,
+    nonce: Res<JoinNonce>,
+    mut snapshot: ResMut<Snapshot>,
+    mut app_state: ResMut<State<AppState>>,
+    mut events: EventWriter<GameEvent>,
+) {
+    for message in link.receive() {
+        match message {
+            NetMessage::Welcome { nonce: n, player } if n == nonce.0 => {
+                info!("Joined the game as player {}", player + 1);
+                *role = NetRole::Client {
+                    player: Some(player),
+                };
+            }
+            NetMessage::Snapshot(new_snapshot) => {
+                // Only the last snapshot of a frame is applied, but every event counts
+                events.send_batch(new_snapshot.events.iter().copied());
+                *snapshot = new_snapshot;
+            }
+            _ => {}
+        }
+    }
+    if !snapshot.is_changed() || app_state.is_changed() {
+        return;
+    }
+    let state = app_state.current().clone();
+    match (state, snapshot.in_game) {
+        (AppState::Start, true) => app_state.set(AppState::Terrain).unwrap(),
+        // The host already went back to the start screen and began a new run
+        (AppState::Finish, true) => app_state.set(AppState::Start).unwrap(),
+        (AppState::Terrain | AppState::Panel, false) => app_state.set(AppState::Cutscene).unwrap(),
+        _ => {}
+    }
+}
+
+/// Tells the players when the host stops and resumes the game
+fn client_show_pause(
+    snapshot: Res<Snapshot>,
+    mut paused: Local<bool>,
+    localization: Res<Localization>,
+    mut toasts: EventWriter<Toast>,
+) {
+    if snapshot.paused == *paused {
+        return;
+    }
+    *paused = snapshot.paused;
+    toasts.send(Toast(localization.get(if *paused {
+        "net-host-paused"
+    } else {
+        "net-host-resumed"
+    })));
+}
+
+/// Snapshots are applied once the game on this side has been set up
+fn snapshot_ready(
+    snapshot: Res<Snapshot>,
+    state: Res<State<AppState>>,
+    terrain: Query<(), With<TerrainSprite>>,
+) -> ShouldRun {
+    match (state.current(), snapshot.is_changed() && snapshot.in_game) {
+        (AppState::Terrain | AppState::Panel, true) if !terrain.is_empty() => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn client_apply_snapshot(
+    mut commands: Commands,
+    snapshot: Res<Snapshot>,
+    role: Res<NetRole>,
+    mut buggies: Query<(&Buggy, &mut Transform, &mut Velocity, &mut Helium), Without<Center>>,
+    mut centers: Query<(&SlotNumber, &mut HarvesterState, &mut Helium), With<Center>>,
+    mut timer: Query<&mut EndTimer>,
+    mut storage: ResMut<StorageHelium>,
+    mut canisters: ResMut<StoredCanisters>,
+    mut harvesters: ResMut<TotalHarvesters>,
+    mut slot_sprites: SlotSprites,
+    terrain_assets: Res<TerrainAssetHandlers>,
+    panel_assets: Res<PanelAssetHandlers>,
+    mut app_state: ResMut<State<AppState>>,
+    mut previous: Local<(f32, HashMap<usize, (Vec2, f32)>)>,
+) {
+    let NetRole::Client { player: me } = *role else {return};
+
+    // The run clock of the host, it stands still while the host is paused
+    let (previous_secs, previous_places) = &mut *previous;
+    let secs = snapshot.elapsed_secs - *previous_secs;
+    *previous_secs = snapshot.elapsed_secs;
+    for remote in snapshot.buggies.iter() {
+        let Some((_, mut transform, mut velocity, mut helium)) =
+            buggies.iter_mut().find(|(b, ..)| b.player == remote.player)
+        else {
+            let buggy = spawn_buggy(&mut commands, &terrain_assets, remote.player);
+            // Physics for every buggy runs on the host
+            commands
+                .entity(buggy)
+                .insert((RigidBody::KinematicVelocityBased, Mirrored));
+            if me == Some(remote.player) {
+                commands.entity(buggy).insert(LocalControl(0));
+            }
+            continue;
+        };
+        let place = Vec3::from_array(remote.translation).truncate();
+        // Keeps going the same way until the next snapshot, and the wheels and the engine follow
+        *velocity = match previous_places.insert(remote.player, (place, remote.rotation)) {
+            Some((from, rotation)) if secs > 0.0 => Velocity {
+                linvel: (place - from) / secs,
+                angvel: ((remote.rotation - rotation + PI).rem_euclid(TAU) - PI) / secs,
+            },
+            _ => Velocity::zero(),
+        };
+        transform.translation = Vec3::from_array(remote.translation);
+        transform.rotation = Quat::from_rotation_z(remote.rotation);
+        helium.0 = remote.helium;
+    }
+
+    for remote in snapshot.centers.iter() {
+        if remote.slot >= harvesters.0 {
+            place_harvester(
+                &mut commands,
+                &terrain_assets,
+                &panel_assets,
+                &mut slot_sprites,
+                &mut harvesters,
+                remote.cell,
+            );
+            continue;
+        }
+        if let Some((_, mut state, mut helium)) =
+            centers.iter_mut().find(|(s, ..)| s.0 == remote.slot)
+        {
+            *state = remote.state;
+            helium.0 = remote.helium;
+        }
+    }
+
+    if let Ok(mut timer) = timer.get_single_mut() {
+        timer
+            .timer
+            .set_elapsed(Duration::from_secs_f32(snapshot.elapsed_secs));
+    }
+    storage.0 = snapshot.storage;
+    while canisters.0 < snapshot.canisters {
+        spawn_canister(&mut commands, &panel_assets, canisters.0);
+        canisters.0 += 1;
+    }
+    if canisters.0 >= CANISTERS_TO_WIN {
+        let _ = app_state.set(AppState::Cutscene);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn client_send_input(
+    link: Res<NetLink>,
+    buggies: Query<(&Buggy, &BuggyInput), (With<LocalControl>, Changed<BuggyInput>)>,
+    mut actions: EventReader<PlayerAction>,
+) {
+    for (buggy, input) in buggies.iter() {
+        link.send(&NetMessage::Input {
+            player: buggy.player,
+            input: *input,
+        });
+    }
+    for action in actions.iter() {
+        link.send(&NetMessage::Action(*action));
+    }
+}
@@ -0,0 +1,205 @@
+//! Colors of the harvester states for the palette picked in the settings.
+//!
+//! The art shows the states in green, yellow and red. Any other palette paints those pixels
+//! over in the atlas and puts a mark on every lamp, map icon and slot, so the state can
+//! be told by its shape too.
+
+use bevy::utils::HashMap;
+
+use crate::{
+    atlas::Atlas,
+    harvester::{Center, CenterIcon, HarvesterState, LampId, SlotIcon, SlotNumber},
+    panel::PanelMarker,
+    settings::{GameplaySettings, Palette},
+    terrain::TerrainMarker,
+    util::PanelAssetHandlers,
+};
+
+use super::*;
+
+/// Pixels greyer than this are outlines and highlights, they keep their color
+const MIN_SATURATION: f32 = 0.25;
+
+impl Palette {
+    pub fn color(self, state: HarvesterState) -> Color {
+        use HarvesterState::*;
+        match (self, state) {
+            // Picked from the art
+            (Palette::Standard, Work) => Color::rgb_u8(0x5a, 0x93, 0x2f),
+            (Palette::Standard, Full) => Color::rgb_u8(0xed, 0xb1, 0x2e),
+            (Palette::Standard, Broken) => Color::rgb_u8(0xac, 0x32, 0x32),
+            // Blue and vermillion stay apart without red or green cones
+            (Palette::Deuteranopia, Work) => Color::rgb_u8(0x00, 0x72, 0xb2),
+            (Palette::Protanopia, Work) => Color::rgb_u8(0x56, 0xb4, 0xe9),
+            (Palette::Deuteranopia | Palette::Protanopia, Full) => Color::rgb_u8(0xf0, 0xe4, 0x42),
+            (Palette::Deuteranopia | Palette::Protanopia, Broken) => {
+                Color::rgb_u8(0xd5, 0x5e, 0x00)
+            }
+            // Yellow looks like pink or white without blue cones, so white it is
+            (Palette::Tritanopia, Work) => Color::rgb_u8(0x00, 0x72, 0xb2),
+            (Palette::Tritanopia, Full) => Color::rgb_u8(0xf5, 0xf5, 0xf5),
+            (Palette::Tritanopia, Broken) => Color::rgb_u8(0xd5, 0x5e, 0x00),
+        }
+    }
+}
+
+/// Shows the shape of the state of `center` over a lamp, a map icon or a slot
+#[derive(Component)]
+struct StateMark {
+    center: Entity,
+}
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(recolor_states)
+            .add_system(mark_states)
+            .add_system(show_state_marks.after(mark_states));
+    }
+}
+
+/// Every frame of the layers drawn in a state color, with its state
+fn state_layers(atlas: &Atlas) -> Vec<(usize, HarvesterState)> {
+    use HarvesterState::*;
+    let states = [("green", Work), ("yellow", Full), ("red", Broken)];
+    let lamps = states.map(|(color, state)| ("spritecenter1.aseprite", color.to_string(), state));
+    let icons = states.map(|(color, state)| ("iconcenter3.aseprite", color.to_string(), state));
+    let slots = (1..=6).flat_map(|slot| {
+        states.map(|(color, state)| ("spritepanel8.aseprite", format!("harv{slot}{color}"), state))
+    });
+    lamps
+        .into_iter()
+        .chain(icons)
+        .chain(slots)
+        .flat_map(|(file, layer, state)| {
+            let regions = atlas
+                .files
+                .get(file)
+                .and_then(|frames| frames.layer(&layer));
+            regions
+                .unwrap_or_default()
+                .iter()
+                .map(move |region| (*region, state))
+        })
+        .collect()
+}
+
+/// Paints the state layers in the atlas over with the palette, from the pixels of the art
+fn recolor_states(
+    gameplay: Res<GameplaySettings>,
+    atlas: Res<Atlas>,
+    layouts: Res<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut painted: Local<Option<Palette>>,
+    mut art: Local<HashMap<usize, Vec<u8>>>,
+) {
+    let palette = gameplay.palette;
+    if *painted == Some(palette) {
+        return;
+    }
+    let Some(layout) = layouts.get(&atlas.layout) else {return};
+    let Some(texture) = images.get_mut(&layout.texture) else {return};
+    let atlas_width = layout.size.x as usize;
+
+    for (region, state) in state_layers(&atlas) {
+        let rect = layout.textures[region];
+        let (x, y) = (rect.min.x as usize, rect.min.y as usize);
+        let (width, height) = (rect.width() as usize, rect.height() as usize);
+        let rows = |line: usize| {
+            let start = ((y + line) * atlas_width + x) * 4;
+            start..start + width * 4
+        };
+        let original = art.entry(region).or_insert_with(|| {
+            (0..height)
+                .flat_map(|line| texture.data[rows(line)].to_vec())
+                .collect()
+        });
+
+        let [_, from_saturation, from_lightness, _] = Palette::Standard.color(state).as_hsla_f32();
+        let [hue, to_saturation, to_lightness, _] = palette.color(state).as_hsla_f32();
+        for line in 0..height {
+            let pixels = original[line * width * 4..(line + 1) * width * 4]
+                .chunks(4)
+                .flat_map(|pixel| {
+                    let color = Color::rgba_u8(pixel[0], pixel[1], pixel[2], pixel[3]);
+                    let [_, saturation, lightness, alpha] = color.as_hsla_f32();
+                    if palette == Palette::Standard || saturation < MIN_SATURATION {
+                        return pixel.to_vec();
+                    }
+                    // The shading of the art is kept around the new color
+                    Color::hsla(
+                        hue,
+                        (saturation * to_saturation / from_saturation).clamp(0.0, 1.0),
+                        (lightness + to_lightness - from_lightness).clamp(0.0, 1.0),
+                        alpha,
+                    )
+                    .as_rgba_f32()
+                    .map(|channel| (channel * 255.0).round() as u8)
+                    .to_vec()
+                })
+                .collect::<Vec<_>>();
+            texture.data[rows(line)].copy_from_slice(&pixels);
+        }
+    }
+    *painted = Some(palette);
+}
+
+/// Puts the marks on the lamp, the map icon and the slot of every new harvester
+fn mark_states(
+    mut commands: Commands,
+    centers: Query<(Entity, &LampId, &CenterIcon, &SlotIcon, &SlotNumber), Added<Center>>,
+    panel_assets: Res<PanelAssetHandlers>,
+) {
+    let mark = &panel_assets.state_marks[0];
+    for (center, lamp, icon, slot_icon, slot) in centers.iter() {
+        let slots = &panel_assets.layout.slots;
+        let on_slot = slots[slot.0.min(slots.len() - 1)].center();
+        for (parent, translation) in [
+            (lamp.0, Vec3::Z * 0.1),
+            (icon.0, Vec3::Z * 0.1),
+            // The slot layers cover the whole panel
+            (slot_icon.0, on_slot.extend(0.1)),
+        ] {
+            let mut entity = commands.spawn((
+                SpriteSheetBundle {
+                    transform: Transform::from_translation(translation),
+                    ..mark.sprite_sheet()
+                },
+                StateMark { center },
+            ));
+            // Despawned with the screen they're on
+            if parent == lamp.0 {
+                entity.insert(TerrainMarker);
+            } else {
+                entity.insert(PanelMarker);
+            }
+            let entity = entity.id();
+            commands.entity(parent).add_child(entity);
+        }
+    }
+}
+
+fn show_state_marks(
+    gameplay: Res<GameplaySettings>,
+    centers: Query<&HarvesterState>,
+    mut marks: Query<(&StateMark, &mut TextureAtlasSprite, &mut Visibility)>,
+    panel_assets: Res<PanelAssetHandlers>,
+) {
+    for (mark, mut sprite, mut visibility) in marks.iter_mut() {
+        let Ok(state) = centers.get(mark.center) else {continue};
+        let index = panel_assets.state_marks[match state {
+            HarvesterState::Work => 0,
+            HarvesterState::Full => 1,
+            HarvesterState::Broken => 2,
+        }]
+        .index;
+        if sprite.index != index {
+            sprite.index = index;
+        }
+        let shown = gameplay.palette != Palette::Standard;
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+    }
+}
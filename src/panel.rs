@@ -1,25 +1,25 @@
-use std::io::Cursor;
-
-use bevy::{
-    ecs::query::ROQueryItem, render::camera::RenderTarget, sprite::collide_aabb::collide,
-    utils::HashSet,
-};
+use bevy::utils::HashSet;
 
 use crate::{
-    buggy::Buggy,
+    atlas::Slice,
+    buggy::{player_color, Buggy},
+    events::GameEvent,
     harvester::{
         add_harvester, Cell, CenterIcon, SlotIcon, SlotNumber, StorageHelium, StoredCanisters,
         TotalHarvesters,
     },
+    i18n::{Localization, Localized},
+    in_game,
+    net::NetRole,
+    picking::{Clickable, Clicked, Cursor},
+    scaling::UiCamera,
     start::EndTimer,
     terrain::{
-        CANISTERS_TO_WIN, HELIUM_TO_BUILD_HARVESTER, HELIUM_TO_MAKE_CANISTER, MAX_HELIUM_STORAGE,
+        PlayerAction, CANISTERS_TO_WIN, HELIUM_TO_BUILD_HARVESTER, HELIUM_TO_MAKE_CANISTER,
+        MAX_HELIUM_STORAGE,
     },
     tooltip::TooltipString,
-    util::{
-        bevy_image_from_ase_image, get_cursor_pos_in_world_coord, PanelAssetHandlers,
-        TerrainAssetHandlers,
-    },
+    util::{PanelAssetHandlers, PanelLayout, TerrainAssetHandlers},
 };
 
 use super::*;
@@ -42,8 +42,11 @@ struct PanelState {
     building_harvester: bool,
 }
 
+/// Where `buggy` is on the map, whoever drives it
 #[derive(Component)]
-struct BuggyIcon;
+struct BuggyIcon {
+    buggy: Entity,
+}
 
 #[derive(Component)]
 struct TankLevel;
@@ -55,13 +58,13 @@ struct CanisterButtonSprite;
 struct CanisterButtonText;
 
 #[derive(Component)]
-struct CanisterButtonSensor;
+pub struct CanisterButtonSensor;
 
 #[derive(Component)]
 struct StoredCanister;
 
 #[derive(Component)]
-struct HarvesterBlueprint;
+pub struct HarvesterBlueprint;
 
 #[derive(Component)]
 pub struct SwitchToTerrainButton;
@@ -88,58 +91,55 @@ impl Plugin for PanelPlugin {
                     .with_system(move_buggy_on_map)
                     .with_system(handle_harv_blueprint.after(mouse_clicks_panel))
                     .with_system(mouse_clicks_panel)
-                    .with_system(canister_builder)
                     .with_system(update_ship)
                     .with_system(update_tank_level),
             )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .with_system(build_harvesters)
+                    .with_system(canister_builder),
+            )
+            .add_system(spawn_buggy_icons)
             .add_event::<StopBuildingHarvesters>()
-            .add_event::<EnterBuildingHarvestersMode>()
-            .add_event::<MakeCanister>();
+            .add_event::<EnterBuildingHarvestersMode>();
     }
 }
 
-pub fn tank_center() -> Vec3 {
-    Vec3 {
-        z: 1.0,
-        ..PANEL_OFFSET
-    } + Vec3 {
-        x: 122.0 - 80.0,
-        y: 60.0 - 103.0,
-        z: 0.0,
-    } * PIXEL_MULTIPLIER
+pub fn tank_center(layout: &PanelLayout) -> Vec3 {
+    PANEL_OFFSET + layout.gauge.center().extend(1.0)
 }
 
-fn set_up_panel(
-    mut commands: Commands,
-    mut textures: ResMut<Assets<Image>>,
-    panel_assets: Res<PanelAssetHandlers>,
-) {
-    let ase_file = asefile::AsepriteFile::read(Cursor::new(include_bytes!(
-        "../assets/spritepanel8.aseprite"
-    )))
-    .expect("valid aseprite");
-    let main_panel = bevy_image_from_ase_image(
-        ase_file
-            .layer_by_name("main")
-            .expect("main layer")
-            .frame(0)
-            .image(),
-    );
-
-    let panel_size = main_panel.size() * PIXEL_MULTIPLIER;
+/// Where a slice of the panel is in the world
+fn on_panel(slice: &Slice, z: f32) -> Vec3 {
+    PANEL_OFFSET + slice.center().extend(z)
+}
 
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            custom_size: Some(panel_size),
-            ..default()
-        },
+fn set_up_panel(mut commands: Commands, panel_assets: Res<PanelAssetHandlers>) {
+    let layout = &panel_assets.layout;
+    commands.spawn(SpriteSheetBundle {
         transform: Transform {
             translation: PANEL_OFFSET,
             ..default()
         },
-        texture: textures.add(main_panel),
-        ..default()
+        ..panel_assets.main.sprite_sheet()
     });
+    let mut stars = panel_assets.stars.clone();
+    // The stars twinkle slower than the frames are timed in the file
+    stars.speed = 0.25;
+    commands.spawn((
+        SpriteSheetBundle {
+            transform: Transform {
+                translation: Vec3 {
+                    z: 0.5,
+                    ..PANEL_OFFSET
+                },
+                ..default()
+            },
+            ..panel_assets.space[0].sprite_sheet()
+        },
+        stars,
+    ));
     commands.spawn((
         Camera2dBundle {
             camera: Camera {
@@ -168,11 +168,7 @@ fn set_up_panel(
         commands.spawn((
             PanelMarker,
             SlotNumber(i),
-            SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(empty.1),
-                    ..default()
-                },
+            SpriteSheetBundle {
                 transform: Transform {
                     translation: Vec3 {
                         z: 1.0,
@@ -180,18 +176,13 @@ fn set_up_panel(
                     },
                     ..default()
                 },
-                texture: empty.0.clone(),
-                ..default()
+                ..empty.sprite_sheet()
             },
         ));
     }
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.exit.1),
-                ..default()
-            },
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3 {
                     x: PANEL_OFFSET.x,
@@ -200,37 +191,27 @@ fn set_up_panel(
                 },
                 ..default()
             },
-            texture: panel_assets.exit.0.clone(),
-            ..default()
+            ..panel_assets.exit.sprite_sheet()
         })
         .insert(PanelMarker);
 
     commands
         .spawn(SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(17.0 * PIXEL_MULTIPLIER, 11.0 * PIXEL_MULTIPLIER)),
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3 {
-                    x: PANEL_OFFSET.x - WIDTH / 2.0 + 11.0 * PIXEL_MULTIPLIER,
-                    y: PANEL_OFFSET.y + HEIGHT / 2.0 - 8.0 * PIXEL_MULTIPLIER,
-                    z: -100.0,
-                },
+                custom_size: Some(layout.exit.size()),
                 ..default()
             },
+            transform: Transform::from_translation(on_panel(&layout.exit, -100.0)),
             ..default()
         })
         .insert(SwitchToTerrainButton)
-        .insert(TooltipString("Back to vehicle".to_string()))
+        .insert(Clickable)
+        .insert(TooltipString::default())
+        .insert(Localized("back-to-vehicle"))
         .insert(PanelMarker);
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.harvester_button[0].1),
-                ..default()
-            },
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3 {
                     z: 1.0,
@@ -238,27 +219,20 @@ fn set_up_panel(
                 },
                 ..default()
             },
-            texture: panel_assets.harvester_button[0].0.clone(),
-            ..default()
+            ..panel_assets.harvester_button[0].sprite_sheet()
         })
         .insert(PanelMarker);
 
     commands.spawn((
         BuildHarvesterButtonSensor,
+        Clickable,
         SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(42.0 * PIXEL_MULTIPLIER, 9.0 * PIXEL_MULTIPLIER)),
+                custom_size: Some(layout.harvester.size()),
                 color: Color::rgba(0.0, 1.0, 1.0, 0.0),
                 ..default()
             },
-            transform: Transform {
-                translation: Vec3 {
-                    x: PANEL_OFFSET.x - WIDTH / 2.0 + 135.0 * PIXEL_MULTIPLIER,
-                    y: PANEL_OFFSET.y + HEIGHT / 2.0 - 77.5 * PIXEL_MULTIPLIER,
-                    z: 3.0,
-                },
-                ..default()
-            },
+            transform: Transform::from_translation(on_panel(&layout.harvester, 3.0)),
             ..default()
         },
         PanelMarker,
@@ -267,11 +241,7 @@ fn set_up_panel(
     commands
         .spawn((
             HarvesterButtonText,
-            SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(panel_assets.harvester_button[1].1),
-                    ..default()
-                },
+            SpriteSheetBundle {
                 transform: Transform {
                     translation: Vec3 {
                         z: 2.0,
@@ -279,43 +249,21 @@ fn set_up_panel(
                     },
                     ..default()
                 },
-                texture: panel_assets.harvester_button[1].0.clone(),
-                ..default()
+                ..panel_assets.harvester_button[1].sprite_sheet()
             },
         ))
         .insert(PanelMarker);
 
-    commands.spawn((
-        BuggyIcon,
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.buggy_icon.1),
-                ..default()
-            },
-            texture: panel_assets.buggy_icon.0.clone(),
-            transform: Transform {
-                translation: Vec3 {
-                    z: 2.0,
-                    ..PANEL_OFFSET
-                },
-                ..default()
-            },
-            ..default()
-        },
-    ));
     commands.spawn((
         TankLevel,
         SpriteBundle {
             sprite: Sprite {
                 color: Color::SEA_GREEN,
-                custom_size: Some(Vec2 {
-                    x: 2.0 * PIXEL_MULTIPLIER,
-                    y: 20.0 * PIXEL_MULTIPLIER,
-                }),
+                custom_size: Some(layout.gauge.size()),
                 ..default()
             },
             transform: Transform {
-                translation: tank_center(),
+                translation: tank_center(layout),
                 ..default()
             },
             ..default()
@@ -324,11 +272,7 @@ fn set_up_panel(
 
     commands.spawn((
         CanisterButtonSprite,
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.tank_button[0].1),
-                ..default()
-            },
+        SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3 {
                     z: 1.0,
@@ -336,18 +280,13 @@ fn set_up_panel(
                 },
                 ..default()
             },
-            texture: panel_assets.tank_button[0].0.clone(),
-            ..default()
+            ..panel_assets.tank_button[0].sprite_sheet()
         },
     ));
 
     commands.spawn((
         CanisterButtonText,
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.tank_button[1].1),
-                ..default()
-            },
+        SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3 {
                     z: 2.0,
@@ -355,37 +294,26 @@ fn set_up_panel(
                 },
                 ..default()
             },
-            texture: panel_assets.tank_button[1].0.clone(),
-            ..default()
+            ..panel_assets.tank_button[1].sprite_sheet()
         },
     ));
 
     commands.spawn((
         CanisterButtonSensor,
+        Clickable,
         SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(27.0 * PIXEL_MULTIPLIER, 9.0 * PIXEL_MULTIPLIER)),
+                custom_size: Some(layout.tank.size()),
                 color: Color::rgba(0.0, 1.0, 1.0, 0.0),
                 ..default()
             },
-            transform: Transform {
-                translation: Vec3 {
-                    x: PANEL_OFFSET.x - WIDTH / 2.0 + 142.5 * PIXEL_MULTIPLIER,
-                    y: PANEL_OFFSET.y + HEIGHT / 2.0 - 89.5 * PIXEL_MULTIPLIER,
-                    z: 3.0,
-                },
-                ..default()
-            },
+            transform: Transform::from_translation(on_panel(&layout.tank, 3.0)),
             ..default()
         },
     ));
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(panel_assets.ship.1),
-                ..default()
-            },
+        .spawn(SpriteSheetBundle {
             transform: Transform {
                 translation: Vec3 {
                     x: PANEL_OFFSET.x - WIDTH / 2.0 + 40.0,
@@ -394,8 +322,7 @@ fn set_up_panel(
                 },
                 ..default()
             },
-            texture: panel_assets.ship.0.clone(),
-            ..default()
+            ..panel_assets.ship.sprite_sheet()
         })
         .insert(Ship {
             start: Vec3 {
@@ -409,13 +336,15 @@ fn set_up_panel(
                 z: 3.0,
             },
         })
-        .insert(TooltipString("ship".to_string()))
+        .insert(Clickable)
+        // Filled in by `update_ship`
+        .insert(TooltipString::default())
         .insert(PanelMarker);
 }
 
 fn enable_panel_cam(
     mut panel_cam: Query<&mut Camera, With<PanelMarker>>,
-    mut other_cams: Query<&mut Camera, Without<PanelMarker>>,
+    mut other_cams: Query<&mut Camera, (Without<PanelMarker>, Without<UiCamera>)>,
 ) {
     panel_cam.for_each_mut(|mut c| c.is_active = true);
     other_cams.for_each_mut(|mut c| c.is_active = false);
@@ -423,7 +352,13 @@ fn enable_panel_cam(
 
 struct StopBuildingHarvesters;
 struct EnterBuildingHarvestersMode;
-struct MakeCanister;
+
+pub type SlotSprites<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut TextureAtlasSprite, &'static SlotNumber),
+    With<PanelMarker>,
+>;
 
 fn toggle_building(
     mut commands: Commands,
@@ -439,14 +374,7 @@ fn toggle_building(
 
         if panel_state.building_harvester {
             commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(panel_assets.center_icon[0].1),
-                        ..default()
-                    },
-                    texture: panel_assets.center_icon[0].0.clone(),
-                    ..default()
-                },
+                panel_assets.center_icon[0].sprite_sheet(),
                 HarvesterBlueprint,
             ));
         } else {
@@ -462,31 +390,46 @@ fn toggle_building(
     }
 }
 
-#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
 fn handle_harv_blueprint(
-    mut commands: Commands,
-    mut harv_blueprint: Query<(&mut Transform, &mut Handle<Image>), With<HarvesterBlueprint>>,
-    wnds: Res<Windows>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut harv_blueprint: Query<(&mut Transform, &mut TextureAtlasSprite), With<HarvesterBlueprint>>,
+    cursor: Res<Cursor>,
     buttons: Res<Input<MouseButton>>,
-    terrain_assets: Res<TerrainAssetHandlers>,
     panel_assets: Res<PanelAssetHandlers>,
     mut stopper: EventWriter<StopBuildingHarvesters>,
-    mut harvesters: ResMut<TotalHarvesters>,
-    mut slot_sprites: Query<
-        (Entity, &mut Handle<Image>, &SlotNumber),
-        (With<PanelMarker>, Without<HarvesterBlueprint>),
-    >,
+    mut actions: EventWriter<PlayerAction>,
     panel_state: Res<PanelState>,
-    mut helium: ResMut<StorageHelium>,
+    helium: Res<StorageHelium>,
     occupied_cells: Query<&Cell>,
 ) {
-    let Some((camera, camera_transform)) = q_camera.iter().find(|(c,_)|c.is_active) else {return};
-    let Some(world_cursor_pos) = get_cursor_pos_in_world_coord(wnds.get_primary().unwrap(), camera_transform, camera) else {return};
+    let Some(world_cursor_pos) = cursor.0 else {return};
 
-    let (cell_coord, world_coord_on_panel) =
-        panel_coord_to_cell_and_snapped_panel_world_coord(world_cursor_pos);
+    let (cell_coord, world_coord_on_panel) = panel_coord_to_cell_and_snapped_panel_world_coord(
+        &panel_assets.layout.map,
+        world_cursor_pos,
+    );
+
+    let overlaps = cell_overlaps(cell_coord, &occupied_cells);
+
+    let new_img = &panel_assets.center_icon[match overlaps {
+        true => 2,
+        false => 0,
+    }];
+
+    harv_blueprint.for_each_mut(|(mut t, mut sprite)| {
+        t.translation = world_coord_on_panel.extend(2.0);
+        sprite.index = new_img.index;
+    });
+    if buttons.just_pressed(MouseButton::Left) && panel_state.building_harvester && !overlaps {
+        if helium.0 >= HELIUM_TO_BUILD_HARVESTER {
+            actions.send(PlayerAction::BuildHarvester { cell: cell_coord });
+        }
+        stopper.send(StopBuildingHarvesters);
+    }
+}
 
+/// Harvesters can't be placed in the same or a neighbouring cell of another harvester
+fn cell_overlaps(cell_coord: (i8, i8), occupied_cells: &Query<&Cell>) -> bool {
     let mut occupied_cells_with_neigh = HashSet::new();
     for Cell((x, y)) in occupied_cells.iter() {
         for neigh_x in -1..=1 {
@@ -496,252 +439,297 @@ fn handle_harv_blueprint(
         }
     }
 
-    let mut overlaps = false;
-
-    'neigh: for neigh_x in -1..=1 {
+    for neigh_x in -1..=1 {
         for neigh_y in -1..=1 {
             let coord = (cell_coord.0 + neigh_x, cell_coord.1 + neigh_y);
             if occupied_cells_with_neigh.contains(&coord) {
-                overlaps = true;
-                break 'neigh;
+                return true;
             }
         }
     }
+    false
+}
 
-    let (new_img, _size) = &panel_assets.center_icon[match overlaps {
-        true => 2,
-        false => 0,
-    }];
-
-    harv_blueprint.for_each_mut(|(mut t, mut img)| {
-        t.translation = world_coord_on_panel.extend(2.0);
-        *img = new_img.clone()
-    });
-    if buttons.just_pressed(MouseButton::Left) && panel_state.building_harvester && !overlaps {
-        if helium.0 < HELIUM_TO_BUILD_HARVESTER {
-            stopper.send(StopBuildingHarvesters);
-            return;
+#[allow(clippy::too_many_arguments)]
+fn build_harvesters(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    terrain_assets: Res<TerrainAssetHandlers>,
+    panel_assets: Res<PanelAssetHandlers>,
+    mut harvesters: ResMut<TotalHarvesters>,
+    mut slot_sprites: SlotSprites,
+    mut helium: ResMut<StorageHelium>,
+    occupied_cells: Query<&Cell>,
+    role: Res<NetRole>,
+    mut events: EventWriter<GameEvent>,
+) {
+    if !role.is_authoritative() {
+        actions.clear();
+        return;
+    }
+    for action in actions.iter() {
+        let PlayerAction::BuildHarvester { cell } = *action else {continue};
+        if helium.0 < HELIUM_TO_BUILD_HARVESTER || cell_overlaps(cell, &occupied_cells) {
+            continue;
         }
+        helium.0 -= HELIUM_TO_BUILD_HARVESTER;
+        events.send(GameEvent::HarvesterBuilt { slot: harvesters.0 });
+        place_harvester(
+            &mut commands,
+            &terrain_assets,
+            &panel_assets,
+            &mut slot_sprites,
+            &mut harvesters,
+            cell,
+        );
+    }
+}
 
-        let (slot_entity, mut slot_image_handler, slot_number) = {
-            let s = slot_sprites
-                .iter_mut()
-                .find(|(_e, _h, slot_number)| slot_number.0 == harvesters.0);
-            match s {
-                Some(s) => s,
-                None => slot_sprites.iter_mut().last().unwrap(),
-            }
-        };
+/// Spawns a harvester on the terrain together with its icon on the map and its slot
+pub fn place_harvester(
+    commands: &mut Commands,
+    terrain_assets: &TerrainAssetHandlers,
+    panel_assets: &PanelAssetHandlers,
+    slot_sprites: &mut SlotSprites,
+    harvesters: &mut TotalHarvesters,
+    cell_coord: (i8, i8),
+) {
+    let (slot_entity, mut slot_image_handler, slot_number) = {
+        let s = slot_sprites
+            .iter_mut()
+            .find(|(_e, _h, slot_number)| slot_number.0 == harvesters.0);
+        match s {
+            Some(s) => s,
+            None => slot_sprites.iter_mut().last().unwrap(),
+        }
+    };
 
-        *slot_image_handler = panel_assets.harv_slots[slot_number.0][1].0.clone();
+    slot_image_handler.index = panel_assets.harv_slots[slot_number.0][1].index;
 
-        let center_icon = commands
-            .spawn(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(panel_assets.center_icon[0].1),
-                    ..default()
-                },
-                texture: panel_assets.center_icon[0].0.clone(),
+    let center_icon = commands
+        .spawn((
+            SpriteSheetBundle {
                 transform: Transform {
-                    translation: world_coord_on_panel.extend(1.0),
+                    translation: cell_to_panel_world_coord(&panel_assets.layout.map, cell_coord)
+                        .extend(1.0),
                     ..default()
                 },
-                ..default()
-            })
-            .id();
-
-        helium.0 -= HELIUM_TO_BUILD_HARVESTER;
-        add_harvester(
-            commands,
-            terrain_assets,
-            cell_coord,
-            harvesters.0,
-            SlotIcon(slot_entity),
-            CenterIcon(center_icon),
-        );
-        harvesters.0 += 1;
-        stopper.send(StopBuildingHarvesters);
-    }
+                ..panel_assets.center_icon[0].sprite_sheet()
+            },
+            panel_assets.center_icon_frames[0].clone(),
+        ))
+        .id();
+
+    add_harvester(
+        commands,
+        terrain_assets,
+        cell_coord,
+        harvesters.0,
+        SlotIcon(slot_entity),
+        CenterIcon(center_icon),
+    );
+    harvesters.0 += 1;
 }
 
 #[allow(clippy::too_many_arguments)]
 fn mouse_clicks_panel(
-    wnds: Res<Windows>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut clicks: EventReader<Clicked>,
     mut buttons: ResMut<Input<MouseButton>>,
     mut app_state: ResMut<State<AppState>>,
-    terrain_button: Query<(&Transform, &Sprite), With<SwitchToTerrainButton>>,
-    harvester_button: Query<(&Transform, &Sprite), With<BuildHarvesterButtonSensor>>,
-    canister_button: Query<(&Transform, &Sprite), With<CanisterButtonSensor>>,
+    terrain_button: Query<(), With<SwitchToTerrainButton>>,
+    harvester_button: Query<(), With<BuildHarvesterButtonSensor>>,
+    canister_button: Query<(), With<CanisterButtonSensor>>,
     helium: Res<StorageHelium>,
     mut building_starter: EventWriter<EnterBuildingHarvestersMode>,
-    mut canister_builder: EventWriter<MakeCanister>,
+    mut actions: EventWriter<PlayerAction>,
 ) {
-    let Some((camera, camera_transform)) = q_camera.iter().find(|(c,_)|c.is_active) else {return};
-
-    let wnd = if let RenderTarget::Window(id) = camera.target {
-        wnds.get(id).unwrap()
-    } else {
-        wnds.get_primary().unwrap()
-    };
+    for Clicked(entity) in clicks.iter() {
+        if terrain_button.contains(*entity) {
+            app_state.set(AppState::Terrain).unwrap();
+            buttons.clear();
+            return;
+        }
 
-    if buttons.just_pressed(MouseButton::Left) {
-        let cursor_collider = Vec2 { x: 1.0, y: 1.0 };
-        if let Some(screen_pos) = wnd.cursor_position() {
-            let window_size = Vec2::new(wnd.width(), wnd.height());
-            let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
-            let ndc_to_world =
-                camera_transform.compute_matrix() * camera.projection_matrix().inverse();
-            let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
-
-            let clicks_sprite = |q: ROQueryItem<(&Transform, &Sprite)>| {
-                collide(
-                    q.0.translation,
-                    Vec2 {
-                        x: q.1.custom_size.unwrap().x,
-                        y: q.1.custom_size.unwrap().y,
-                    },
-                    world_pos,
-                    cursor_collider,
-                )
-                .is_some()
-            };
-
-            if clicks_sprite(terrain_button.single()) {
-                app_state.set(AppState::Terrain).unwrap();
-                buttons.clear();
-                return;
-            }
+        if harvester_button.contains(*entity) && helium.0 >= HELIUM_TO_BUILD_HARVESTER {
+            building_starter.send(EnterBuildingHarvestersMode);
+            buttons.clear();
+        }
 
-            if clicks_sprite(harvester_button.single()) && helium.0 >= HELIUM_TO_BUILD_HARVESTER {
-                building_starter.send(EnterBuildingHarvestersMode);
-                buttons.clear();
-            }
+        if canister_button.contains(*entity) && helium.0 >= HELIUM_TO_MAKE_CANISTER {
+            actions.send(PlayerAction::MakeCanister);
+            // The last canister starts the cutscene, which skips on a click
+            buttons.clear();
+        }
+    }
+}
 
-            if clicks_sprite(canister_button.single()) && helium.0 >= HELIUM_TO_MAKE_CANISTER {
-                canister_builder.send(MakeCanister);
-            }
+/// Every buggy gets an icon in its player's color, the ones driven on other machines too
+fn spawn_buggy_icons(
+    mut commands: Commands,
+    buggies: Query<(Entity, &Buggy), Added<Buggy>>,
+    removed: RemovedComponents<Buggy>,
+    icons: Query<(Entity, &BuggyIcon)>,
+    panel_assets: Res<PanelAssetHandlers>,
+) {
+    for (buggy, Buggy { player }) in buggies.iter() {
+        commands.spawn((
+            BuggyIcon { buggy },
+            SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    color: player_color(*player),
+                    ..panel_assets.buggy_icon.sprite()
+                },
+                transform: Transform {
+                    translation: Vec3 {
+                        z: 2.0,
+                        ..PANEL_OFFSET
+                    },
+                    ..default()
+                },
+                ..panel_assets.buggy_icon.sprite_sheet()
+            },
+            PanelMarker,
+        ));
+    }
+    for buggy in removed.iter() {
+        for (entity, _) in icons.iter().filter(|(_, icon)| icon.buggy == buggy) {
+            commands.entity(entity).despawn();
         }
     }
 }
 
 fn move_buggy_on_map(
-    buggy: Query<&Transform, With<Buggy>>,
-    mut buggy_icon: Query<&mut Transform, (With<BuggyIcon>, Without<Buggy>)>,
+    buggies: Query<&Transform, With<Buggy>>,
+    mut buggy_icons: Query<(&BuggyIcon, &mut Transform), Without<Buggy>>,
+    panel_assets: Res<PanelAssetHandlers>,
 ) {
-    let Ok(pos) = buggy.get_single() else {return};
-    let Ok(mut buggy_icon_pos) = buggy_icon.get_single_mut() else {return};
+    // The middle of the terrain is the middle of the map
+    let map_center = on_panel(&panel_assets.layout.map, 0.0);
 
-    // center of the grid - center of the sprite
-    let center_offset = Vec2 {
-        x: (80.0 - 55.0) * PIXEL_MULTIPLIER,
-        y: (79.0 - 60.0) * PIXEL_MULTIPLIER,
-    };
-
-    buggy_icon_pos.translation = pos.translation / CELL_SIZE_TERRAIN * CELL_SIZE_PANEL
-        + PANEL_OFFSET
-        - center_offset.extend(0.0);
+    for (icon, mut buggy_icon_pos) in buggy_icons.iter_mut() {
+        let Ok(pos) = buggies.get(icon.buggy) else {continue};
+        buggy_icon_pos.translation =
+            pos.translation / CELL_SIZE_TERRAIN * CELL_SIZE_PANEL + map_center;
+    }
 }
 
-fn panel_coord_to_cell_and_snapped_panel_world_coord(world_coord: Vec2) -> ((i8, i8), Vec2) {
+/// The grid lines take the left column and the top row of every cell of the map
+const CELL_INSIDE_OFFSET: Vec2 = Vec2::new(0.5, -0.5);
+
+fn panel_coord_to_cell_and_snapped_panel_world_coord(
+    map: &Slice,
+    world_coord: Vec2,
+) -> ((i8, i8), Vec2) {
     let step = CELL_SIZE_PANEL * PIXEL_MULTIPLIER;
-    let icon_to_panel_sprite_offset = Vec2 { x: 5.5, y: 5.5 } * PIXEL_MULTIPLIER;
-    let icon_to_panel_center_offset = Vec2 { x: -8.0, y: -6.0 };
+    let cells = (map.size() / step).floor();
+
+    let cell_coord = ((world_coord - PANEL_OFFSET.truncate() - map.bottom_left()) / step).floor();
 
-    let cell_on_panel = ((world_coord - icon_to_panel_sprite_offset) / step).round() * step
-        + icon_to_panel_sprite_offset;
+    // The cells on the edges are cut off by the frame
+    let clamped_cell_coord = cell_coord.clamp(Vec2::ONE, cells - 2.0);
+    let cell = (clamped_cell_coord.x as i8, clamped_cell_coord.y as i8);
 
-    let cell_coord = (cell_on_panel - PANEL_OFFSET.truncate() - icon_to_panel_sprite_offset) / step
-        - icon_to_panel_center_offset;
+    (cell, cell_to_panel_world_coord(map, cell))
+}
 
-    let clamped_cell_coord = cell_coord.clamp(Vec2 { x: 1.0, y: 1.0 }, Vec2 { x: 9.0, y: 6.0 });
+fn cell_to_panel_world_coord(map: &Slice, cell: (i8, i8)) -> Vec2 {
+    let cell = Vec2::new(cell.0 as f32, cell.1 as f32);
 
-    let world_coord_on_panel = (clamped_cell_coord + icon_to_panel_center_offset) * step
-        + icon_to_panel_sprite_offset
-        + PANEL_OFFSET.truncate();
-    (
-        (clamped_cell_coord.x as i8, clamped_cell_coord.y as i8),
-        world_coord_on_panel,
-    )
+    ((cell + 0.5) * CELL_SIZE_PANEL + CELL_INSIDE_OFFSET) * PIXEL_MULTIPLIER
+        + map.bottom_left()
+        + PANEL_OFFSET.truncate()
 }
 
 fn update_tank_level(
     mut tank: Query<(&mut Sprite, &mut Transform), With<TankLevel>>,
     helium: Res<StorageHelium>,
-    mut new_harv_button: Query<&mut Handle<Image>, With<HarvesterButtonText>>,
+    mut new_harv_button: Query<&mut TextureAtlasSprite, With<HarvesterButtonText>>,
     mut canister_button: Query<
-        &mut Handle<Image>,
+        &mut TextureAtlasSprite,
         (With<CanisterButtonText>, Without<HarvesterButtonText>),
     >,
     panel_assets: Res<PanelAssetHandlers>,
 ) {
-    let max_tank_height_px = 20.0;
+    let gauge = &panel_assets.layout.gauge;
+    let max_tank_height_px = gauge.size().y / PIXEL_MULTIPLIER;
     let progress = helium.0 as f32 / MAX_HELIUM_STORAGE as f32;
     let height = (max_tank_height_px * progress).round();
 
     let (mut sprite, mut transform) = tank.single_mut();
 
-    transform.translation.y = tank_center().y - (10.0 - height / 2.0) * PIXEL_MULTIPLIER;
+    transform.translation.y = tank_center(&panel_assets.layout).y
+        - (max_tank_height_px - height) / 2.0 * PIXEL_MULTIPLIER;
     sprite.custom_size.as_mut().unwrap().y = height * PIXEL_MULTIPLIER;
     let button_text_img_idx = match helium.0 >= HELIUM_TO_BUILD_HARVESTER {
         true => 2,
         false => 1,
     };
-    *new_harv_button.single_mut() = panel_assets.harvester_button[button_text_img_idx].0.clone();
+    new_harv_button.single_mut().index = panel_assets.harvester_button[button_text_img_idx].index;
 
-    *canister_button.single_mut() =
+    canister_button.single_mut().index =
         panel_assets.tank_button[match helium.0 >= HELIUM_TO_MAKE_CANISTER {
             true => 2,
             false => 1,
         }]
-        .0
-        .clone();
+        .index;
 }
 
+#[allow(clippy::too_many_arguments)]
 fn canister_builder(
     mut commands: Commands,
-    mut canister_event: EventReader<MakeCanister>,
+    mut actions: EventReader<PlayerAction>,
     mut helium: ResMut<StorageHelium>,
     mut stored_canisters: ResMut<StoredCanisters>,
     panel_assets: Res<PanelAssetHandlers>,
     mut state: ResMut<State<AppState>>,
+    role: Res<NetRole>,
+    mut events: EventWriter<GameEvent>,
 ) {
-    for _ in canister_event.iter() {
+    if !role.is_authoritative() {
+        actions.clear();
+        return;
+    }
+    for action in actions.iter() {
+        let PlayerAction::MakeCanister = action else {continue};
         if helium.0 < HELIUM_TO_MAKE_CANISTER {
             return;
         }
         helium.0 -= HELIUM_TO_MAKE_CANISTER;
-        let (can_img, size) =
-            &panel_assets.tanks[stored_canisters.0.min(panel_assets.tanks.len() - 1)];
-        commands.spawn((
-            StoredCanister,
-            SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(*size),
-                    ..default()
-                },
-                texture: can_img.clone(),
-                transform: Transform {
-                    translation: Vec3 {
-                        z: 1.0,
-                        ..PANEL_OFFSET
-                    },
-                    ..default()
-                },
-                ..default()
-            },
-        ));
+        events.send(GameEvent::CanisterCrafted {
+            index: stored_canisters.0,
+        });
+        spawn_canister(&mut commands, &panel_assets, stored_canisters.0);
         stored_canisters.0 += 1;
         if stored_canisters.0 == CANISTERS_TO_WIN {
-            state.set(AppState::Finish).unwrap();
+            state.set(AppState::Cutscene).unwrap();
             return;
         }
     }
 }
 
+/// Puts the canister with the given index on the rack
+pub fn spawn_canister(commands: &mut Commands, panel_assets: &PanelAssetHandlers, index: usize) {
+    let can_img = &panel_assets.tanks[index.min(panel_assets.tanks.len() - 1)];
+    commands.spawn((
+        StoredCanister,
+        SpriteSheetBundle {
+            transform: Transform {
+                translation: Vec3 {
+                    z: 1.0,
+                    ..PANEL_OFFSET
+                },
+                ..default()
+            },
+            ..can_img.sprite_sheet()
+        },
+    ));
+}
+
 fn update_ship(
     mut ship: Query<(&mut Transform, &mut TooltipString, &Ship), With<Ship>>,
     timer: Query<&EndTimer>,
+    localization: Res<Localization>,
+    panel_assets: Res<PanelAssetHandlers>,
 ) {
     let (mut ship_transform, mut string, ship) = ship.single_mut();
     let timer = timer.single();
@@ -749,8 +737,9 @@ fn update_ship(
         ship.start.x + ((ship.finish.x - ship.start.x) * timer.timer.percent());
     ship_transform.translation.y =
         ship.start.y + ((ship.finish.y - ship.start.y) * timer.timer.percent());
-    string.0 = format!(
-        "{} seconds left before arrival",
-        timer.timer.remaining_secs() as i32
-    );
+    *string = TooltipString::text(localization.format(
+        "ship-arrival",
+        &[("secs", (timer.timer.remaining_secs() as i32).into())],
+    ))
+    .with_icon(&panel_assets.ship, Color::WHITE);
 }
@@ -0,0 +1,188 @@
+use bevy_rapier2d::prelude::RapierConfiguration;
+
+use crate::{i18n::Localized, menu, net::NetRole, panel::HarvesterBlueprint};
+
+use super::*;
+
+const PAUSE_KEYS: [KeyCode; 2] = [KeyCode::Escape, KeyCode::P];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Resume,
+    Settings,
+    Restart,
+    Quit,
+}
+
+impl Row {
+    fn label_id(self) -> &'static str {
+        match self {
+            Row::Resume => "pause-resume",
+            Row::Settings => "pause-settings",
+            Row::Restart => "pause-restart",
+            Row::Quit => "pause-quit",
+        }
+    }
+}
+
+/// Rows shown in this game, the menu is rebuilt every time the game is paused
+#[derive(Component)]
+struct PauseMenu {
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+#[derive(Component)]
+struct RowText(Row);
+
+/// Starts a new run as soon as the start screen is back
+#[derive(Resource)]
+struct RestartRun;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .with_system(pause_game),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Paused)
+                .with_system(spawn_pause_menu)
+                .with_system(stop_physics),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Paused)
+                .with_system(despawn_pause_menu)
+                .with_system(start_physics),
+        )
+        .add_system_set(SystemSet::on_pause(AppState::Paused).with_system(hide_pause_menu))
+        .add_system_set(SystemSet::on_resume(AppState::Paused).with_system(show_pause_menu))
+        .add_system_set(SystemSet::on_update(AppState::Paused).with_system(navigate_pause))
+        .add_system_set(SystemSet::on_update(AppState::Start).with_system(restart_run));
+    }
+}
+
+fn pause_game(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    blueprints: Query<(), With<HarvesterBlueprint>>,
+) {
+    // Escape cancels placing a harvester first
+    if !blueprints.is_empty() {
+        return;
+    }
+    // The run may end on this very frame, then there's nothing left to pause
+    if menu::take_keys(&mut keys, PAUSE_KEYS) {
+        let _ = app_state.push(AppState::Paused);
+    }
+}
+
+fn stop_physics(mut phys: ResMut<RapierConfiguration>) {
+    phys.physics_pipeline_active = false;
+}
+
+fn start_physics(mut phys: ResMut<RapierConfiguration>) {
+    phys.physics_pipeline_active = true;
+}
+
+fn spawn_pause_menu(mut commands: Commands, mut fonts: ResMut<Assets<Font>>, role: Res<NetRole>) {
+    // Everyone else would have to start over too
+    let rows = match *role {
+        NetRole::Offline => vec![Row::Resume, Row::Settings, Row::Restart, Row::Quit],
+        _ => vec![Row::Resume, Row::Settings, Row::Quit],
+    };
+    menu::spawn_menu(
+        &mut commands,
+        &mut fonts,
+        PauseMenu {
+            rows: rows.clone(),
+            selected: 0,
+        },
+        "pause-title",
+        "pause-hint",
+        |menu, text_style| {
+            for row in rows {
+                menu.spawn((
+                    menu::row_bundle(String::new(), text_style),
+                    RowText(row),
+                    Localized(row.label_id()),
+                ));
+            }
+        },
+    );
+}
+
+fn despawn_pause_menu(mut commands: Commands, menu: Query<Entity, With<PauseMenu>>) {
+    menu.for_each(|e| commands.entity(e).despawn_recursive());
+}
+
+/// The settings are opened on top of it
+fn hide_pause_menu(mut menu: Query<&mut Visibility, With<PauseMenu>>) {
+    menu.for_each_mut(|mut v| v.is_visible = false);
+}
+
+fn show_pause_menu(mut menu: Query<&mut Visibility, With<PauseMenu>>) {
+    menu.for_each_mut(|mut v| v.is_visible = true);
+}
+
+fn navigate_pause(
+    mut commands: Commands,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut menu: Query<&mut PauseMenu>,
+    mut texts: Query<(&mut Text, &RowText)>,
+    run_entities: Query<Entity, (Without<Parent>, Without<PauseMenu>)>,
+) {
+    let Ok(mut menu) = menu.get_single_mut() else {return};
+    if menu::take_keys(&mut keys, PAUSE_KEYS) {
+        let _ = app_state.pop();
+        return;
+    }
+    let mut selected = menu.selected;
+    menu::move_selection(&keys, &mut selected, menu.rows.len());
+    if selected != menu.selected || menu.is_added() {
+        menu.selected = selected;
+        for (mut text, RowText(row)) in texts.iter_mut() {
+            text.sections[0].style.color = menu::row_color(*row == menu.rows[menu.selected]);
+        }
+    }
+
+    if !menu::take_keys(&mut keys, [KeyCode::Return, KeyCode::Space]) {
+        return;
+    }
+    match menu.rows[menu.selected] {
+        Row::Resume => {
+            let _ = app_state.pop();
+        }
+        Row::Settings => {
+            let _ = app_state.push(AppState::Settings);
+        }
+        row @ (Row::Restart | Row::Quit) => {
+            // The run stays as it is if another transition got queued first
+            if app_state.replace(AppState::Start).is_err() {
+                return;
+            }
+            // Same as after the finish screen, the start screen sets everything up again
+            run_entities.for_each(|e| commands.entity(e).despawn_recursive());
+            if row == Row::Restart {
+                commands.insert_resource(RestartRun);
+            }
+        }
+    }
+}
+
+fn restart_run(
+    mut commands: Commands,
+    restart: Option<Res<RestartRun>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if restart.is_some() {
+        // Tried again on the next frame if the start screen is busy with another transition
+        if app_state.set(AppState::Terrain).is_ok() {
+            commands.remove_resource::<RestartRun>();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Small JSON documents that outlive a run: a file next to the game on desktop,
+//! localStorage in the browser
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use bevy::prelude::*;
+
+/// Reads the document saved under `key`, falling back to the default when
+/// there is nothing saved yet or it can't be read
+pub fn load<T: DeserializeOwned + Default>(key: &str) -> T {
+    match read(key).map(|json| serde_json::from_str(&json)) {
+        Some(Ok(value)) => value,
+        Some(Err(e)) => {
+            warn!("Ignoring saved {key}: {e}");
+            T::default()
+        }
+        None => T::default(),
+    }
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    let json = serde_json::to_string_pretty(value).expect("serializable document");
+    if let Err(e) = write(key, &json) {
+        warn!("Can't save {key}: {e}");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(key: &str) -> String {
+    format!("{key}.json")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(path(key)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, json: &str) -> Result<(), String> {
+    std::fs::write(path(key), json).map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(key: &str) -> String {
+    format!("moon2023.{key}")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Option<String> {
+    storage()?.get_item(&storage_key(key)).ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, json: &str) -> Result<(), String> {
+    let storage = storage().ok_or("no localStorage")?;
+    storage
+        .set_item(&storage_key(key), json)
+        .map_err(|e| format!("{e:?}"))
+}
@@ -0,0 +1,157 @@
+//! Finds what the cursor points at through the active camera, so clicks and tooltips
+//! don't each convert the cursor and test the sprites themselves.
+
+use bevy::{input::InputSystem, render::camera::RenderTarget};
+
+use crate::{scaling::GameCameras, util::get_cursor_pos_in_world_coord};
+
+use super::*;
+
+/// Sprites that can be clicked or hovered, the topmost one under the cursor gets the events
+#[derive(Component)]
+pub struct Clickable;
+
+/// Left button pressed over the entity
+pub struct Clicked(pub Entity);
+
+/// Sent every frame the cursor is over the entity
+pub struct Hovered(pub Entity);
+
+/// Where the cursor is in the world of the active camera
+#[derive(Resource, Default)]
+pub struct Cursor(pub Option<Vec2>);
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Clicked>()
+            .add_event::<Hovered>()
+            .init_resource::<Cursor>()
+            .add_system_to_stage(CoreStage::PreUpdate, pick.after(InputSystem));
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn pick(
+    wnds: Res<Windows>,
+    q_camera: GameCameras,
+    buttons: Res<Input<MouseButton>>,
+    objects: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<(&Sprite, &Handle<Image>)>,
+            Option<(&TextureAtlasSprite, &Handle<TextureAtlas>)>,
+        ),
+        With<Clickable>,
+    >,
+    images: Res<Assets<Image>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut cursor: ResMut<Cursor>,
+    mut clicks: EventWriter<Clicked>,
+    mut hovers: EventWriter<Hovered>,
+) {
+    cursor.0 = q_camera
+        .iter()
+        .find(|(c, _)| c.is_active)
+        .and_then(|(camera, camera_transform)| {
+            let wnd = if let RenderTarget::Window(id) = camera.target {
+                wnds.get(id)?
+            } else {
+                wnds.get_primary()?
+            };
+            get_cursor_pos_in_world_coord(wnd, camera_transform, camera)
+        });
+    let Some(world_pos) = cursor.0 else {return};
+
+    let topmost = objects
+        .iter()
+        .filter(|(_, transform, sprite, atlas_sprite)| {
+            // Plain sprites for the sensors, the art comes from the atlas
+            let picture = match (sprite, atlas_sprite) {
+                (Some((sprite, image)), _) => Picture {
+                    image: images.get(image),
+                    rect: sprite.rect,
+                    size: sprite.custom_size,
+                    flip: BVec2::new(sprite.flip_x, sprite.flip_y),
+                    anchor: sprite.anchor.as_vec(),
+                },
+                (None, Some((sprite, atlas))) => {
+                    let atlas = atlases.get(atlas);
+                    Picture {
+                        image: atlas.and_then(|atlas| images.get(&atlas.texture)),
+                        rect: atlas.and_then(|atlas| atlas.textures.get(sprite.index).copied()),
+                        size: sprite.custom_size,
+                        flip: BVec2::new(sprite.flip_x, sprite.flip_y),
+                        anchor: sprite.anchor.as_vec(),
+                    }
+                }
+                (None, None) => return false,
+            };
+            picture.hits(transform, world_pos)
+        })
+        .max_by(|(_, a, ..), (_, b, ..)| a.translation().z.total_cmp(&b.translation().z));
+    let Some((entity, ..)) = topmost else {return};
+
+    hovers.send(Hovered(entity));
+    if buttons.just_pressed(MouseButton::Left) {
+        clicks.send(Clicked(entity));
+    }
+}
+
+/// The pixels a sprite shows, to look up the one under the cursor
+struct Picture<'a> {
+    image: Option<&'a Image>,
+    /// Part of the image, all of it when `None`
+    rect: Option<Rect>,
+    /// In world units, the size of `rect` when `None`
+    size: Option<Vec2>,
+    flip: BVec2,
+    /// From the center, in sizes of the sprite
+    anchor: Vec2,
+}
+
+impl Picture<'_> {
+    /// Whether `point` lands on a pixel that isn't transparent, turned and scaled along with the sprite.
+    /// Images that aren't loaded yet count as solid
+    fn hits(&self, transform: &GlobalTransform, point: Vec2) -> bool {
+        let rect = self.rect.or_else(|| {
+            let image = self.image?;
+            Some(Rect::from_corners(Vec2::ZERO, image.size()))
+        });
+        let Some(size) = self.size.or(rect.map(|rect| rect.size())) else {
+            return false;
+        };
+
+        let local = transform
+            .affine()
+            .inverse()
+            .transform_point3(point.extend(transform.translation().z))
+            .truncate();
+        // 0 to 1 from the top left corner, like the pixels of the image
+        let mut uv = local / size + self.anchor + 0.5;
+        uv.y = 1.0 - uv.y;
+        if uv.cmplt(Vec2::ZERO).any() || uv.cmpge(Vec2::ONE).any() {
+            return false;
+        }
+        if self.flip.x {
+            uv.x = 1.0 - uv.x;
+        }
+        if self.flip.y {
+            uv.y = 1.0 - uv.y;
+        }
+
+        let (Some(image), Some(rect)) = (self.image, rect) else {
+            return true;
+        };
+        let pixel = (rect.min + uv * rect.size()).floor();
+        let width = image.size().x as usize;
+        let index = (pixel.y as usize * width + pixel.x as usize) * 4 + 3;
+        // Only 8 bit RGBA is read, anything else is solid
+        if image.data.len() != width * image.size().y as usize * 4 {
+            return true;
+        }
+        image.data.get(index).is_none_or(|alpha| *alpha > 0)
+    }
+}
@@ -0,0 +1,115 @@
+//! The art is 160x120 pixels, every camera draws it scaled by the biggest whole number
+//! that fits the window and the rest of the window stays black around it.
+//!
+//! The UI is laid out over the whole window, so it gets its own camera
+//! and the game cameras leave it out.
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    render::{
+        camera::{CameraUpdateSystem, ScalingMode, Viewport},
+        view::RenderLayers,
+    },
+};
+
+use super::*;
+
+/// Size of the art in its own pixels, the world is `PIXEL_MULTIPLIER` times bigger
+pub const NATIVE_SIZE: UVec2 = UVec2::new(160, 120);
+
+/// Draws only the UI, over the game and the bars around it
+#[derive(Component)]
+pub struct UiCamera;
+
+/// The cameras that draw the game, the active one is what the cursor points into
+pub type GameCameras<'w, 's> =
+    Query<'w, 's, (&'static Camera, &'static GlobalTransform), (With<Camera2d>, Without<UiCamera>)>;
+
+pub struct ScalingPlugin;
+
+impl Plugin for ScalingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_ui_camera).add_system_to_stage(
+            CoreStage::PostUpdate,
+            fit_to_window.before(CameraUpdateSystem),
+        );
+    }
+}
+
+/// The finish screen despawns every entity, so the camera comes back on demand
+fn spawn_ui_camera(mut commands: Commands, cameras: Query<(), With<UiCamera>>) {
+    if !cameras.is_empty() {
+        return;
+    }
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                priority: 1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        // Nothing is drawn on the last layer
+        RenderLayers::layer(RenderLayers::TOTAL_LAYERS as u8 - 1),
+        UiCamera,
+    ));
+}
+
+/// Letterboxes every game camera and scales the UI along with the art
+fn fit_to_window(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    mut cameras: Query<
+        (
+            Entity,
+            &mut Camera,
+            &mut OrthographicProjection,
+            Option<&UiCameraConfig>,
+        ),
+        Without<UiCamera>,
+    >,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let Some(window) = windows.get_primary() else {return};
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    // Minimized
+    if window_size.min_element() == 0 {
+        return;
+    }
+    let scale = (window_size / NATIVE_SIZE).min_element().max(1);
+    let size = (NATIVE_SIZE * scale).min(window_size);
+    let position = (window_size - size) / 2;
+
+    for (entity, mut camera, mut projection, ui) in cameras.iter_mut() {
+        let fitted = camera
+            .viewport
+            .as_ref()
+            .is_some_and(|v| v.physical_position == position && v.physical_size == size);
+        if !fitted {
+            camera.viewport = Some(Viewport {
+                physical_position: position,
+                physical_size: size,
+                ..default()
+            });
+        }
+        // `scale` is left to the zoom of the buggy camera
+        if !matches!(projection.scaling_mode, ScalingMode::FixedVertical(height) if height == HEIGHT)
+        {
+            projection.scaling_mode = ScalingMode::FixedVertical(HEIGHT);
+        }
+        if ui.is_none() {
+            commands
+                .entity(entity)
+                .insert(UiCameraConfig { show_ui: false });
+        }
+    }
+
+    // The UI was laid out for `PIXEL_MULTIPLIER` screen pixels per pixel of the art
+    let ui = scale as f64 / PIXEL_MULTIPLIER as f64 / window.scale_factor();
+    if ui_scale.scale != ui {
+        ui_scale.scale = ui;
+    }
+}
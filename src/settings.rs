@@ -0,0 +1,620 @@
+use std::ops::RangeInclusive;
+
+use bevy::window::WindowMode;
+use bevy_kira_audio::{AudioChannel, AudioControl};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buggy::{BuggyControls, KeyBindings, MAX_PLAYERS},
+    highscore::NameEntry,
+    i18n::{Language, Localization},
+    menu, persist, scaling,
+    sfx::SfxChannel,
+    toast::Toast,
+};
+
+use super::*;
+
+const SAVE_KEY: &str = "settings";
+/// Music at full volume is still quiet enough to hear the effects over it
+const MUSIC_VOLUME: f64 = 0.2;
+const VOLUME_STEP: f32 = 0.1;
+const MUTE_KEY: KeyCode = KeyCode::M;
+const FULLSCREEN_KEY: KeyCode = KeyCode::F11;
+/// Screen pixels per pixel of the art in the window, `PIXEL_MULTIPLIER` is the size the game was drawn for
+const PIXEL_SCALES: RangeInclusive<u32> = 2..=10;
+const TOOLTIP_DELAY_STEP: f32 = 0.25;
+const MAX_TOOLTIP_DELAY: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+const DISPLAY_MODES: [DisplayMode; 3] = [
+    DisplayMode::Windowed,
+    DisplayMode::Borderless,
+    DisplayMode::Fullscreen,
+];
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub mode: DisplayMode,
+    pub pixel_scale: u32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        DisplaySettings {
+            mode: DisplayMode::Windowed,
+            pixel_scale: PIXEL_MULTIPLIER as u32,
+        }
+    }
+}
+
+/// Colors of the harvester states, the standard red, yellow and green are hard to tell apart for many
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+const PALETTES: [Palette; 4] = [
+    Palette::Standard,
+    Palette::Deuteranopia,
+    Palette::Protanopia,
+    Palette::Tritanopia,
+];
+
+#[derive(Resource, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub language: Language,
+    pub palette: Palette,
+    /// Seconds the cursor has to rest on something before its tooltip shows
+    pub tooltip_delay: f32,
+}
+
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn master_volume(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else {
+            self.master as f64
+        }
+    }
+
+    pub fn music_volume(&self) -> f64 {
+        self.master_volume() * self.music as f64 * MUSIC_VOLUME
+    }
+
+    /// Sounds played with their own volume have to be scaled by this,
+    /// the channel volume only applies to the rest
+    pub fn sfx_volume(&self) -> f64 {
+        self.master_volume() * self.sfx as f64
+    }
+}
+
+/// Everything kept in the settings file, every part is a resource of its own
+/// so changing one doesn't wake up the systems using the others
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    display: DisplaySettings,
+    audio: AudioSettings,
+    controls: KeyBindings,
+    gameplay: GameplaySettings,
+}
+
+impl SettingsFile {
+    /// `step` is -1 or 1, toggles ignore the direction
+    fn change(&mut self, row: Row, step: i32) {
+        let volume = |volume: f32| ((volume / VOLUME_STEP).round() + step as f32) * VOLUME_STEP;
+        match row {
+            Row::WindowMode => self.display.mode = cycle(&DISPLAY_MODES, self.display.mode, step),
+            Row::PixelScale => {
+                self.display.pixel_scale = self
+                    .display
+                    .pixel_scale
+                    .saturating_add_signed(step)
+                    .clamp(*PIXEL_SCALES.start(), *PIXEL_SCALES.end())
+            }
+            Row::Master => self.audio.master = volume(self.audio.master).clamp(0.0, 1.0),
+            Row::Music => self.audio.music = volume(self.audio.music).clamp(0.0, 1.0),
+            Row::Sfx => self.audio.sfx = volume(self.audio.sfx).clamp(0.0, 1.0),
+            Row::Mute => self.audio.muted = !self.audio.muted,
+            Row::Language => {
+                self.gameplay.language = cycle(&Language::ALL, self.gameplay.language, step)
+            }
+            Row::Palette => self.gameplay.palette = cycle(&PALETTES, self.gameplay.palette, step),
+            Row::TooltipDelay => {
+                let delay =
+                    (self.gameplay.tooltip_delay / TOOLTIP_DELAY_STEP).round() + step as f32;
+                self.gameplay.tooltip_delay =
+                    (delay * TOOLTIP_DELAY_STEP).clamp(0.0, MAX_TOOLTIP_DELAY)
+            }
+            Row::Page | Row::Key { .. } | Row::Back => {}
+        }
+    }
+
+    /// The key is taken away from whatever had it before, that gets the replaced key instead
+    fn bind(&mut self, player: usize, action: usize, key: KeyCode) {
+        let replaced = self.controls.0[player].keys()[action];
+        for controls in &mut self.controls.0 {
+            for bound in controls.keys_mut() {
+                if *bound == key {
+                    *bound = replaced;
+                }
+            }
+        }
+        *self.controls.0[player].keys_mut()[action] = key;
+    }
+
+    /// Name and value of a row, the page switcher and Back have no value
+    fn label(
+        &self,
+        row: Row,
+        page: Page,
+        rebinding: bool,
+        localization: &Localization,
+    ) -> (String, Option<String>) {
+        let get = |id| localization.get(id);
+        let (name, value) = match row {
+            Row::Page => return (format!("< {} >", get(page.title_id())), None),
+            Row::Back => return (get("settings-back"), None),
+            Row::WindowMode => (
+                get("setting-window"),
+                get(match self.display.mode {
+                    DisplayMode::Windowed => "window-windowed",
+                    DisplayMode::Borderless => "window-borderless",
+                    DisplayMode::Fullscreen => "window-fullscreen",
+                }),
+            ),
+            Row::PixelScale => (
+                get("setting-window-size"),
+                format!("{}x", self.display.pixel_scale),
+            ),
+            Row::Master => (get("setting-master-volume"), volume_bar(self.audio.master)),
+            Row::Music => (get("setting-music-volume"), volume_bar(self.audio.music)),
+            Row::Sfx => (get("setting-sound-volume"), volume_bar(self.audio.sfx)),
+            Row::Mute => (
+                localization.format("setting-mute", &[("key", key_name(MUTE_KEY).into())]),
+                get(if self.audio.muted {
+                    "setting-on"
+                } else {
+                    "setting-off"
+                }),
+            ),
+            Row::Key { player, action } => (
+                localization.format(
+                    "setting-key",
+                    &[
+                        ("player", (player + 1).into()),
+                        ("action", get(ACTION_IDS[action]).into()),
+                    ],
+                ),
+                if rebinding {
+                    get("setting-press-key")
+                } else {
+                    key_name(self.controls.0[player].keys()[action])
+                },
+            ),
+            Row::Language => (
+                get("setting-language"),
+                self.gameplay.language.native_name().to_string(),
+            ),
+            Row::Palette => (
+                get("setting-colors"),
+                get(match self.gameplay.palette {
+                    Palette::Standard => "palette-standard",
+                    Palette::Deuteranopia => "palette-deuteranopia",
+                    Palette::Protanopia => "palette-protanopia",
+                    Palette::Tritanopia => "palette-tritanopia",
+                }),
+            ),
+            Row::TooltipDelay => (
+                get("setting-tooltip-delay"),
+                match self.gameplay.tooltip_delay {
+                    delay if delay > 0.0 => localization.format(
+                        "tooltip-delay-secs",
+                        &[("secs", format!("{delay:.2}").into())],
+                    ),
+                    _ => get("tooltip-delay-off"),
+                },
+            ),
+        };
+        (name, Some(value))
+    }
+}
+
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, step: i32) -> T {
+    let index = all.iter().position(|t| *t == current).unwrap_or(0);
+    all[(index as i32 + step).rem_euclid(all.len() as i32) as usize]
+}
+
+fn volume_bar(volume: f32) -> String {
+    let filled = (volume / VOLUME_STEP).round() as usize;
+    let steps = (1.0 / VOLUME_STEP).round() as usize;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(steps - filled))
+}
+
+/// In the order of `BuggyControls::keys`
+const ACTION_IDS: [&str; 5] = [
+    "action-forward",
+    "action-brake",
+    "action-left",
+    "action-right",
+    "action-interact",
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::LControl => "LCtrl".to_string(),
+        KeyCode::RControl => "RCtrl".to_string(),
+        key => format!("{key:?}").trim_start_matches("Key").to_string(),
+    }
+}
+
+/// Short description of a player's keys for the start screen
+pub fn controls_text(controls: &BuggyControls) -> String {
+    let [forward, back, left, right, interact] = controls.keys().map(key_name);
+    format!("{forward}/{left}/{back}/{right}, {interact}")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Page {
+    #[default]
+    Display,
+    Audio,
+    Controls,
+    Gameplay,
+}
+
+const PAGES: [Page; 4] = [Page::Display, Page::Audio, Page::Controls, Page::Gameplay];
+
+impl Page {
+    fn title_id(self) -> &'static str {
+        match self {
+            Page::Display => "page-display",
+            Page::Audio => "page-audio",
+            Page::Controls => "page-controls",
+            Page::Gameplay => "page-gameplay",
+        }
+    }
+
+    /// Every page starts with the page switcher and ends with Back
+    fn rows(self) -> Vec<Row> {
+        let options = match self {
+            Page::Display => vec![Row::WindowMode, Row::PixelScale],
+            Page::Audio => vec![Row::Master, Row::Music, Row::Sfx, Row::Mute],
+            Page::Controls => (0..MAX_PLAYERS)
+                .flat_map(|player| {
+                    (0..ACTION_IDS.len()).map(move |action| Row::Key { player, action })
+                })
+                .collect(),
+            Page::Gameplay => vec![Row::Language, Row::Palette, Row::TooltipDelay],
+        };
+        [vec![Row::Page], options, vec![Row::Back]].concat()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Page,
+    WindowMode,
+    PixelScale,
+    Master,
+    Music,
+    Sfx,
+    Mute,
+    Key { player: usize, action: usize },
+    Language,
+    Palette,
+    TooltipDelay,
+    Back,
+}
+
+#[derive(Component)]
+struct SettingsMarker;
+
+/// Rows of every page are spawned up front and hidden while another page is shown
+#[derive(Component)]
+struct RowText {
+    row: Row,
+    /// `None` for the rows shared by all pages
+    page: Option<Page>,
+}
+
+#[derive(Resource, Default)]
+struct SettingsMenu {
+    page: Page,
+    /// Row of the current page picked with the arrow keys
+    selected: usize,
+    /// The next key pressed goes to the selected row
+    rebinding: bool,
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let SettingsFile {
+            display,
+            audio,
+            controls,
+            gameplay,
+        } = persist::load(SAVE_KEY);
+        app.insert_resource(display)
+            .insert_resource(audio)
+            .insert_resource(controls)
+            .insert_resource(gameplay)
+            .add_system(apply_display_settings)
+            .add_system(apply_audio_settings)
+            .add_system(save_settings)
+            .add_system(toggle_mute)
+            .add_system(toggle_fullscreen)
+            .add_system_set(SystemSet::on_enter(AppState::Settings).with_system(spawn_settings))
+            .add_system_set(SystemSet::on_exit(AppState::Settings).with_system(despawn_settings))
+            .add_system_set(
+                SystemSet::on_update(AppState::Settings).with_system(navigate_settings),
+            );
+    }
+}
+
+/// The window only exists once the app runs, so the settings are applied from a system
+fn apply_display_settings(
+    display: Res<DisplaySettings>,
+    mut windows: ResMut<Windows>,
+    mut applied: Local<bool>,
+) {
+    if *applied && !display.is_changed() {
+        return;
+    }
+    let Some(window) = windows.get_primary_mut() else {return};
+    window.set_mode(match display.mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+        DisplayMode::Fullscreen => WindowMode::Fullscreen,
+    });
+    // Fullscreen fits as many whole screen pixels per pixel of the art as it can by itself
+    if display.mode == DisplayMode::Windowed {
+        let size = scaling::NATIVE_SIZE.as_vec2() * display.pixel_scale as f32
+            / window.backend_scale_factor() as f32;
+        window.set_resolution(size.x, size.y);
+    }
+    *applied = true;
+}
+
+/// The music picks up the changes itself, it mixes its layers with their own volumes
+fn apply_audio_settings(settings: Res<AudioSettings>, sfx: Res<AudioChannel<SfxChannel>>) {
+    if settings.is_changed() {
+        sfx.set_volume(settings.sfx_volume());
+    }
+}
+
+fn save_settings(
+    display: Res<DisplaySettings>,
+    audio: Res<AudioSettings>,
+    controls: Res<KeyBindings>,
+    gameplay: Res<GameplaySettings>,
+) {
+    let changed = display.is_changed()
+        || audio.is_changed()
+        || controls.is_changed()
+        || gameplay.is_changed();
+    // They are all added from the file when the game starts
+    if changed && !display.is_added() {
+        let file = SettingsFile {
+            display: display.clone(),
+            audio: audio.clone(),
+            controls: controls.clone(),
+            gameplay: gameplay.clone(),
+        };
+        persist::save(SAVE_KEY, &file);
+    }
+}
+
+fn toggle_mute(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<AudioSettings>,
+    name_entry: Option<Res<NameEntry>>,
+    localization: Res<Localization>,
+    mut toasts: EventWriter<Toast>,
+) {
+    // M is just a letter while the name is typed
+    if name_entry.is_none() && keys.just_pressed(MUTE_KEY) {
+        settings.muted = !settings.muted;
+        toasts.send(Toast(localization.get(if settings.muted {
+            "sound-off"
+        } else {
+            "sound-on"
+        })));
+    }
+}
+
+/// Flips between a window and borderless fullscreen, the window gets its size from the settings back
+fn toggle_fullscreen(keys: Res<Input<KeyCode>>, mut display: ResMut<DisplaySettings>) {
+    if keys.just_pressed(FULLSCREEN_KEY) {
+        display.mode = match display.mode {
+            DisplayMode::Windowed => DisplayMode::Borderless,
+            DisplayMode::Borderless | DisplayMode::Fullscreen => DisplayMode::Windowed,
+        };
+    }
+}
+
+fn spawn_settings(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+    commands.insert_resource(SettingsMenu::default());
+    // Labels are filled in by `navigate_settings` as soon as the menu is added
+    menu::spawn_menu(
+        &mut commands,
+        &mut fonts,
+        SettingsMarker,
+        "settings-title",
+        "settings-hint",
+        |menu, text_style| {
+            let mut spawn_row = |row, page| {
+                menu.spawn((
+                    menu::row_bundle(String::new(), text_style),
+                    RowText { row, page },
+                ));
+            };
+            spawn_row(Row::Page, None);
+            for page in PAGES {
+                for row in page.rows() {
+                    if !matches!(row, Row::Page | Row::Back) {
+                        spawn_row(row, Some(page));
+                    }
+                }
+            }
+            spawn_row(Row::Back, None);
+        },
+    );
+}
+
+fn despawn_settings(mut commands: Commands, menu: Query<Entity, With<SettingsMarker>>) {
+    menu.for_each(|e| commands.entity(e).despawn_recursive());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn navigate_settings(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut display: ResMut<DisplaySettings>,
+    mut audio: ResMut<AudioSettings>,
+    mut controls: ResMut<KeyBindings>,
+    mut gameplay: ResMut<GameplaySettings>,
+    mut app_state: ResMut<State<AppState>>,
+    localization: Res<Localization>,
+    mut rows: Query<(&mut Text, &mut Style, &RowText)>,
+) {
+    let page = menu.page;
+    let page_rows = page.rows();
+    let row = page_rows[menu.selected];
+    let mut file = SettingsFile {
+        display: display.clone(),
+        audio: audio.clone(),
+        controls: controls.clone(),
+        gameplay: gameplay.clone(),
+    };
+
+    if menu.rebinding {
+        // Escape cancels, any other key is bound
+        if !menu::take_keys(&mut keys, [KeyCode::Escape]) {
+            let Some(&key) = keys.get_just_pressed().next() else {return};
+            keys.clear_just_pressed(key);
+            if let Row::Key { player, action } = row {
+                file.bind(player, action, key);
+            }
+        }
+        menu.rebinding = false;
+    } else {
+        if menu::take_keys(&mut keys, [KeyCode::Escape]) {
+            app_state.pop().unwrap();
+            return;
+        }
+        let mut selected = menu.selected;
+        menu::move_selection(&keys, &mut selected, page_rows.len());
+        if selected != menu.selected {
+            menu.selected = selected;
+        }
+
+        let step = match (
+            keys.just_pressed(KeyCode::Left),
+            keys.just_pressed(KeyCode::Right),
+        ) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => 0,
+        };
+        let activated = menu::take_keys(&mut keys, [KeyCode::Return, KeyCode::Space]);
+        match row {
+            Row::Page if step != 0 => {
+                menu.page = cycle(&PAGES, page, step);
+            }
+            Row::Page => {}
+            Row::Back if activated => {
+                app_state.pop().unwrap();
+                return;
+            }
+            Row::Back => {}
+            Row::Key { .. } if activated => menu.rebinding = true,
+            row if step != 0 => file.change(row, step),
+            row if activated => file.change(row, 1),
+            _ => {}
+        }
+    }
+
+    // Only the parts that really changed are written back, each of them is applied on change
+    if file.display != *display {
+        *display = file.display.clone();
+    }
+    if file.audio != *audio {
+        *audio = file.audio.clone();
+    }
+    if file.controls != *controls {
+        *controls = file.controls.clone();
+    }
+    if file.gameplay != *gameplay {
+        *gameplay = file.gameplay.clone();
+    }
+
+    let changed = menu.is_changed()
+        || display.is_changed()
+        || audio.is_changed()
+        || controls.is_changed()
+        || gameplay.is_changed()
+        || localization.is_changed();
+    if changed {
+        let page = menu.page;
+        let selected_row = page.rows()[menu.selected];
+        // The font is monospace, padding the names to the longest one on the page lines up the values
+        let width = page
+            .rows()
+            .into_iter()
+            .filter_map(|row| {
+                let (name, value) = file.label(row, page, false, &localization);
+                value.map(|_| name.chars().count() + 1)
+            })
+            .max()
+            .unwrap_or(0);
+        for (mut text, mut style, row_text) in rows.iter_mut() {
+            let shown = row_text.page.is_none_or(|p| p == page);
+            style.display = if shown { Display::Flex } else { Display::None };
+            let selected = row_text.row == selected_row;
+            let rebinding = selected && menu.rebinding;
+            text.sections[0].value = match file.label(row_text.row, page, rebinding, &localization)
+            {
+                (name, Some(value)) => format!("{name:<width$}{value}"),
+                (name, None) => name,
+            };
+            text.sections[0].style.color = menu::row_color(selected);
+        }
+    }
+}
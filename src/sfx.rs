@@ -0,0 +1,277 @@
+use bevy::utils::{HashMap, HashSet};
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioInstance, AudioTween};
+use bevy_rapier2d::prelude::{CollisionEvent, Velocity};
+
+use crate::{
+    buggy::{BuggyInput, LocalControl},
+    events::GameEvent,
+    harvester::{Center, HarvesterState},
+    settings::AudioSettings,
+    util::{load_sounds, SoundAssetHandlers},
+};
+
+use super::*;
+
+/// Engine pitch when standing still
+const ENGINE_IDLE_RATE: f64 = 0.6;
+/// How much the pitch goes up per pixel per second of forward speed
+const ENGINE_RATE_PER_SPEED: f64 = 0.004;
+const ENGINE_MAX_RATE: f64 = 2.2;
+/// Braking slower than this is just rolling back and doesn't screech
+const BRAKE_SPEED: f32 = 60.0;
+/// Bumps slower than this make no sound, faster ones are louder up to `LOUDEST_COLLISION_SPEED`
+const QUIETEST_COLLISION_SPEED: f32 = 40.0;
+const LOUDEST_COLLISION_SPEED: f32 = 400.0;
+/// How far away a center can still be heard, alarms carry across the whole terrain
+const HUM_DISTANCE: f32 = WIDTH * 0.75;
+const BEEP_DISTANCE: f32 = WIDTH * 1.5;
+const ALARM_DISTANCE: f32 = WIDTH * 3.0;
+
+/// Every sound effect goes through this channel, the music plays on the main one
+#[derive(Resource)]
+pub struct SfxChannel;
+
+/// Sounds that set their own volume every frame, kept away from the channel volume of `SfxChannel`
+#[derive(Resource)]
+struct SpatialChannel;
+
+/// Looping engine sound of every local buggy
+#[derive(Resource, Default)]
+struct EngineSounds(HashMap<Entity, Handle<AudioInstance>>);
+
+/// Looping sound of every center, swapped when its state changes
+#[derive(Resource, Default)]
+struct CenterSounds(HashMap<Entity, CenterSound>);
+
+struct CenterSound {
+    state: HarvesterState,
+    instance: Handle<AudioInstance>,
+    volume: f64,
+    panning: f64,
+}
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<SfxChannel>()
+            .add_audio_channel::<SpatialChannel>()
+            .init_resource::<EngineSounds>()
+            .init_resource::<CenterSounds>()
+            .add_startup_system(load_sounds)
+            .add_system(engine_sounds)
+            .add_system(center_sounds)
+            .add_system(brake_sounds)
+            .add_system(collision_sounds)
+            .add_system(event_sounds)
+            .add_system(click_sounds)
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(pause_sounds))
+            .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(resume_sounds));
+    }
+}
+
+/// Engines and centers fall silent with the rest of the run, the music keeps playing
+fn pause_sounds(sfx: Res<AudioChannel<SfxChannel>>, spatial: Res<AudioChannel<SpatialChannel>>) {
+    sfx.pause();
+    spatial.pause();
+}
+
+fn resume_sounds(sfx: Res<AudioChannel<SfxChannel>>, spatial: Res<AudioChannel<SpatialChannel>>) {
+    sfx.resume();
+    spatial.resume();
+}
+
+fn forward_speed(velocity: &Velocity, transform: &Transform) -> f32 {
+    velocity
+        .linvel
+        .dot((transform.rotation * Vec3::Y).truncate())
+}
+
+fn engine_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    mut engines: ResMut<EngineSounds>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    buggies: Query<(Entity, &Velocity, &Transform), With<LocalControl>>,
+) {
+    for (entity, velocity, transform) in buggies.iter() {
+        let engine = engines
+            .0
+            .entry(entity)
+            .or_insert_with(|| sfx.play(sounds.engine.clone()).looped().handle());
+        let rate = ENGINE_IDLE_RATE
+            + forward_speed(velocity, transform).abs() as f64 * ENGINE_RATE_PER_SPEED;
+        if let Some(instance) = instances.get_mut(engine) {
+            instance.set_playback_rate(rate.min(ENGINE_MAX_RATE), AudioTween::default());
+        }
+    }
+
+    // The buggies are gone once the run is over
+    engines.0.retain(|entity, engine| {
+        let alive = buggies.contains(*entity);
+        if !alive {
+            if let Some(instance) = instances.get_mut(engine) {
+                instance.stop(AudioTween::default());
+            }
+        }
+        alive
+    });
+}
+
+/// Pans and fades every center's loop as heard from the closest local buggy
+#[allow(clippy::type_complexity)]
+fn center_sounds(
+    spatial: Res<AudioChannel<SpatialChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    settings: Res<AudioSettings>,
+    mut center_sounds: ResMut<CenterSounds>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    centers: Query<(Entity, &HarvesterState, &Transform), With<Center>>,
+    listeners: Query<&Transform, With<LocalControl>>,
+) {
+    for (entity, state, transform) in centers.iter() {
+        let (loop_sound, hearing_distance) = match state {
+            HarvesterState::Work => (&sounds.center_hum, HUM_DISTANCE),
+            HarvesterState::Full => (&sounds.center_full, BEEP_DISTANCE),
+            HarvesterState::Broken => (&sounds.center_alarm, ALARM_DISTANCE),
+        };
+        let play = || CenterSound {
+            state: *state,
+            instance: spatial
+                .play(loop_sound.clone())
+                .looped()
+                .with_volume(0.0)
+                .handle(),
+            volume: 0.0,
+            panning: 0.5,
+        };
+        let sound = center_sounds.0.entry(entity).or_insert_with(play);
+        if sound.state != *state {
+            if let Some(instance) = instances.get_mut(&sound.instance) {
+                instance.stop(AudioTween::default());
+            }
+            *sound = play();
+        }
+
+        let position = transform.translation.truncate();
+        let Some(listener) = listeners.iter().min_by(|a, b| {
+            let a = a.translation.truncate().distance_squared(position);
+            let b = b.translation.truncate().distance_squared(position);
+            a.total_cmp(&b)
+        }) else {continue};
+        let to_center = position - listener.translation.truncate();
+        let distance = to_center.length();
+        let right = (listener.rotation * Vec3::X).truncate();
+        // -1 on the left of the buggy, 1 on its right
+        let side = if distance > 0.0 {
+            to_center.dot(right) / distance
+        } else {
+            0.0
+        };
+        let closeness = (1.0 - distance / hearing_distance).max(0.0);
+        let volume = (closeness * closeness) as f64 * settings.sfx_volume();
+        let panning = 0.5 + side as f64 * 0.4;
+
+        // Sounds only take a few commands per frame
+        let moved = (volume - sound.volume).abs() > 0.01
+            || (panning - sound.panning).abs() > 0.01
+            || settings.is_changed();
+        let Some(instance) = instances.get_mut(&sound.instance) else {continue};
+        if moved {
+            instance.set_volume(volume, AudioTween::default());
+            instance.set_panning(panning, AudioTween::default());
+            sound.volume = volume;
+            sound.panning = panning;
+        }
+    }
+
+    center_sounds.0.retain(|entity, sound| {
+        let alive = centers.contains(*entity);
+        if !alive {
+            if let Some(instance) = instances.get_mut(&sound.instance) {
+                instance.stop(AudioTween::default());
+            }
+        }
+        alive
+    });
+}
+
+fn brake_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    buggies: Query<(Entity, &BuggyInput, &Velocity, &Transform), With<LocalControl>>,
+    mut braking: Local<HashSet<Entity>>,
+) {
+    for (entity, input, velocity, transform) in buggies.iter() {
+        let brakes = input.back && forward_speed(velocity, transform) > BRAKE_SPEED;
+        if brakes && braking.insert(entity) {
+            sfx.play(sounds.brake.clone());
+        }
+        if !brakes {
+            braking.remove(&entity);
+        }
+    }
+}
+
+fn collision_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    settings: Res<AudioSettings>,
+    mut collisions: EventReader<CollisionEvent>,
+    buggies: Query<&Velocity, With<LocalControl>>,
+) {
+    for collision in collisions.iter() {
+        let CollisionEvent::Started(a, b, _) = collision else {continue};
+        let Ok(velocity) = buggies.get(*a).or_else(|_| buggies.get(*b)) else {continue};
+        let loudness = (velocity.linvel.length() - QUIETEST_COLLISION_SPEED)
+            / (LOUDEST_COLLISION_SPEED - QUIETEST_COLLISION_SPEED);
+        if loudness > 0.0 {
+            sfx.play(sounds.collision.clone())
+                .with_volume(loudness.min(1.0) as f64 * settings.sfx_volume());
+        }
+    }
+}
+
+fn event_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    mut events: EventReader<GameEvent>,
+) {
+    for event in events.iter() {
+        let sound = match event {
+            GameEvent::HeliumCollected { .. } => &sounds.pickup,
+            GameEvent::HeliumDelivered { .. } => &sounds.unload,
+            GameEvent::HarvesterRepaired { .. } => &sounds.repair,
+            GameEvent::HarvesterBroke { .. } => &sounds.alarm,
+            GameEvent::CanisterCrafted { .. } => &sounds.canister,
+            GameEvent::HeliumHarvested { .. }
+            | GameEvent::HarvesterFull { .. }
+            | GameEvent::HarvesterBuilt { .. }
+            | GameEvent::StorageFull => continue,
+        };
+        sfx.play(sound.clone());
+    }
+}
+
+/// Every click on the control panel hits a button or the map, and so does switching views
+fn click_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    sounds: Res<SoundAssetHandlers>,
+    buttons: Res<Input<MouseButton>>,
+    state: Res<State<AppState>>,
+    mut last_state: Local<Option<AppState>>,
+    mut clicked_last_frame: Local<bool>,
+) {
+    let current = state.current();
+    let switched_view = matches!(
+        (last_state.as_ref(), current),
+        (Some(AppState::Terrain), AppState::Panel) | (Some(AppState::Panel), AppState::Terrain)
+    );
+    let clicked_panel = *current == AppState::Panel && buttons.just_pressed(MouseButton::Left);
+    // The exit button was already heard when it was clicked
+    if clicked_panel || (switched_view && !*clicked_last_frame) {
+        sfx.play(sounds.click.clone());
+    }
+    *last_state = Some(current.clone());
+    *clicked_last_frame = clicked_panel;
+}
@@ -2,21 +2,65 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 
-use crate::{terrain::TerrainMarker, AppState};
+use crate::{
+    achievements::Achievements,
+    buggy::{KeyBindings, LocalPlayers, MAX_PLAYERS},
+    highscore::HighScores,
+    i18n::{Localization, Localized},
+    settings::controls_text,
+    terrain::TerrainMarker,
+    AppState,
+};
 
 #[derive(Component)]
 pub struct StartMarker;
 
+#[derive(Component)]
+struct PlayersText;
+
+/// Shows either the high scores or the achievements
+#[derive(Component, Default)]
+struct RecordsText {
+    achievements: bool,
+}
+
 pub struct StartPlugin;
 
 impl Plugin for StartPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(SystemSet::on_enter(AppState::Start).with_system(spawn_start));
         app.add_system_set(SystemSet::on_exit(AppState::Start).with_system(despawn_start));
+        app.add_system_set(
+            SystemSet::on_update(AppState::Start)
+                .with_system(toggle_players)
+                .with_system(toggle_records)
+                .with_system(open_settings),
+        );
+        app.insert_resource(LocalPlayers(1));
     }
 }
 
-fn spawn_start(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+fn players_text(players: usize, bindings: &KeyBindings, localization: &Localization) -> String {
+    match players {
+        1 => localization.get("players-one"),
+        n => localization.format(
+            "players-many",
+            &[
+                ("count", n.into()),
+                ("keys", controls_text(&bindings.0[1]).into()),
+            ],
+        ),
+    }
+}
+
+fn spawn_start(
+    mut commands: Commands,
+    mut fonts: ResMut<Assets<Font>>,
+    players: Res<LocalPlayers>,
+    bindings: Res<KeyBindings>,
+    high_scores: Res<HighScores>,
+    localization: Res<Localization>,
+) {
     commands.spawn((Camera2dBundle::default(), StartMarker));
     let font_size = 14.0;
 
@@ -26,7 +70,7 @@ fn spawn_start(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
     commands
         .spawn(
             TextBundle::from_section(
-                include_str!("start_text.txt"),
+                "",
                 TextStyle {
                     font: font_handle.clone(),
                     font_size,
@@ -44,11 +88,55 @@ fn spawn_start(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
                 ..default()
             }),
         )
+        .insert(Localized("start-story"))
         .insert(StartMarker);
+    commands.spawn((
+        TextBundle::from_section(
+            players_text(players.0, &bindings, &localization),
+            TextStyle {
+                font: font_handle.clone(),
+                font_size,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        PlayersText,
+        StartMarker,
+    ));
+    // Between the story and the players toggle
+    commands.spawn((
+        TextBundle::from_section(
+            records_text(&high_scores, None, &localization),
+            TextStyle {
+                font: font_handle.clone(),
+                font_size: 10.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(65.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        RecordsText::default(),
+        StartMarker,
+    ));
     commands
         .spawn(
             TextBundle::from_section(
-                "Moon\n2023".to_uppercase(),
+                "",
                 TextStyle {
                     font: font_handle,
                     font_size: 100.0,
@@ -66,9 +154,72 @@ fn spawn_start(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
                 ..default()
             }),
         )
+        .insert(Localized("game-title"))
         .insert(StartMarker);
 }
 
+fn toggle_players(
+    keys: Res<Input<KeyCode>>,
+    mut players: ResMut<LocalPlayers>,
+    bindings: Res<KeyBindings>,
+    localization: Res<Localization>,
+    mut text: Query<&mut Text, With<PlayersText>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        players.0 = players.0 % MAX_PLAYERS + 1;
+    }
+    // The keys and the language may have been changed in the settings on top of this screen
+    if players.is_changed() || bindings.is_changed() || localization.is_changed() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value = players_text(players.0, &bindings, &localization);
+        }
+    }
+}
+
+fn records_text(
+    high_scores: &HighScores,
+    achievements: Option<&Achievements>,
+    localization: &Localization,
+) -> String {
+    match achievements {
+        Some(achievements) => format!(
+            "{}\n\n{}",
+            achievements.list(localization),
+            localization.get("show-high-scores")
+        ),
+        None => format!(
+            "{}\n\n{}",
+            high_scores.table(localization),
+            localization.get("show-achievements")
+        ),
+    }
+}
+
+fn toggle_records(
+    keys: Res<Input<KeyCode>>,
+    high_scores: Res<HighScores>,
+    achievements: Res<Achievements>,
+    localization: Res<Localization>,
+    mut text: Query<(&mut Text, &mut RecordsText)>,
+) {
+    let toggled = keys.just_pressed(KeyCode::A);
+    if toggled || localization.is_changed() {
+        for (mut text, mut records) in text.iter_mut() {
+            if toggled {
+                records.achievements = !records.achievements;
+            }
+            let shown = records.achievements.then_some(&*achievements);
+            text.sections[0].value = records_text(&high_scores, shown, &localization);
+        }
+    }
+}
+
+fn open_settings(keys: Res<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::O) {
+        app_state.push(AppState::Settings).unwrap();
+    }
+}
+
 fn despawn_start(mut commands: Commands, start_entities: Query<Entity, With<StartMarker>>) {
     start_entities.for_each(|e| commands.entity(e).despawn());
 }
@@ -93,8 +244,9 @@ pub fn check_end(
     let mut timer = timer.single_mut();
     timer.timer.tick(time.delta());
     // println!("{}", timer.timer.remaining_secs());
+    // Checked again after the pause menu if it opened on the same frame
     if timer.timer.finished() {
-        app_state.set(AppState::Finish).unwrap();
+        let _ = app_state.set(AppState::Cutscene);
     }
 }
 
@@ -0,0 +1,257 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy_rapier2d::prelude::Velocity;
+use serde::Serialize;
+
+use crate::{
+    buggy::Buggy,
+    events::GameEvent,
+    harvester::{Center, HarvesterState, StoredCanisters},
+    i18n::Localization,
+    start::EndTimer,
+    terrain::{CANISTERS_TO_WIN, PIXELS_PER_METER},
+};
+
+use super::*;
+
+/// Numbers collected over a single run, shown on the finish screen
+#[derive(Resource, Default, Serialize)]
+pub struct RunStats {
+    pub won: bool,
+    pub score: u32,
+    pub run_secs: f32,
+    /// Left on the clock when the last canister was crafted, zero when the time ran out
+    pub time_left_secs: f32,
+    /// Produced by all the harvesters
+    pub helium_harvested: usize,
+    /// Picked up from the centers by the buggies
+    pub helium_collected: usize,
+    /// Unloaded into the main storage
+    pub helium_delivered: usize,
+    pub breakdowns: usize,
+    pub repairs: usize,
+    /// Summed over all harvesters
+    pub harvester_secs_broken: f32,
+    /// Summed over all harvesters
+    pub harvester_secs_full: f32,
+    pub meters_driven: f32,
+    pub harvesters_built: usize,
+    pub canisters: usize,
+    pub last_canister_secs: Option<f32>,
+    /// Indexed by harvester slot
+    pub harvesters: Vec<HarvesterStats>,
+    /// Indexed by player
+    pub players: Vec<PlayerStats>,
+}
+
+#[derive(Default, Serialize)]
+pub struct HarvesterStats {
+    pub harvested: usize,
+    /// Left for a buggy to pick up when it came by
+    pub collected: usize,
+    pub times_full: usize,
+    pub breakdowns: usize,
+    pub repairs: usize,
+}
+
+#[derive(Default, Serialize)]
+pub struct PlayerStats {
+    pub collected: usize,
+    pub delivered: usize,
+    pub repairs: usize,
+}
+
+/// Grows the list so that `index` is in it
+fn entry<T: Default>(list: &mut Vec<T>, index: usize) -> &mut T {
+    if list.len() <= index {
+        list.resize_with(index + 1, T::default);
+    }
+    &mut list[index]
+}
+
+impl RunStats {
+    pub fn summary(&self, localization: &Localization) -> String {
+        let last_canister = match self.last_canister_secs {
+            Some(secs) => format_time(secs),
+            None => "-".to_string(),
+        };
+        localization.format(
+            "run-summary",
+            &[
+                ("score", self.score.into()),
+                ("harvested", self.helium_harvested.into()),
+                ("delivered", self.helium_delivered.into()),
+                ("repairs", self.repairs.into()),
+                ("breakdowns", self.breakdowns.into()),
+                ("broken", self.harvester_secs_broken.round().into()),
+                ("full", self.harvester_secs_full.round().into()),
+                ("meters", self.meters_driven.round().into()),
+                ("built", self.harvesters_built.into()),
+                ("last", last_canister.into()),
+            ],
+        )
+    }
+
+    /// Canisters matter the most, then how quickly the ship was filled,
+    /// then how little helium was left lying around in the centers and buggies
+    fn compute_score(&self) -> u32 {
+        let efficiency = match self.helium_harvested {
+            0 => 0.0,
+            harvested => (self.helium_delivered as f32 / harvested as f32).min(1.0),
+        };
+        (self.canisters as f32 * SCORE_PER_CANISTER
+            + self.time_left_secs * SCORE_PER_SECOND_LEFT
+            + efficiency * SCORE_FOR_EFFICIENCY)
+            .round() as u32
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serializable stats")
+    }
+}
+
+/// Points for every canister crafted
+const SCORE_PER_CANISTER: f32 = 1000.0;
+/// Points for every second left on the clock after a win
+const SCORE_PER_SECOND_LEFT: f32 = 10.0;
+/// Points for delivering every harvested unit of helium into the storage
+const SCORE_FOR_EFFICIENCY: f32 = 500.0;
+
+/// mm:ss
+pub fn format_time(secs: f32) -> String {
+    let secs = secs as u32;
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_system_set(SystemSet::on_exit(AppState::Start).with_system(reset_stats))
+            .add_system_set(SystemSet::on_enter(AppState::Cutscene).with_system(finish_run))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_run)
+                    .with_system(count_events)
+                    .with_system(track_time_and_distance),
+            );
+    }
+}
+
+/// The last canister is crafted right as the cutscene starts, its event must still be counted
+pub fn in_run(state: Res<State<AppState>>) -> ShouldRun {
+    match state.current() {
+        AppState::Terrain | AppState::Panel | AppState::Cutscene => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+fn reset_stats(mut commands: Commands) {
+    commands.insert_resource(RunStats::default());
+}
+
+pub fn count_events(
+    mut events: EventReader<GameEvent>,
+    mut stats: ResMut<RunStats>,
+    timer: Query<&EndTimer>,
+) {
+    let stats = stats.as_mut();
+    for event in events.iter() {
+        match *event {
+            GameEvent::HeliumHarvested { slot } => {
+                stats.helium_harvested += 1;
+                entry(&mut stats.harvesters, slot).harvested += 1;
+            }
+            GameEvent::HarvesterFull { slot } => entry(&mut stats.harvesters, slot).times_full += 1,
+            GameEvent::HarvesterBroke { slot } => {
+                stats.breakdowns += 1;
+                entry(&mut stats.harvesters, slot).breakdowns += 1;
+            }
+            GameEvent::HarvesterRepaired { player, slot } => {
+                stats.repairs += 1;
+                entry(&mut stats.harvesters, slot).repairs += 1;
+                entry(&mut stats.players, player).repairs += 1;
+            }
+            GameEvent::HeliumCollected {
+                player,
+                slot,
+                amount,
+            } => {
+                stats.helium_collected += amount;
+                entry(&mut stats.harvesters, slot).collected += amount;
+                entry(&mut stats.players, player).collected += amount;
+            }
+            GameEvent::HeliumDelivered { player, amount } => {
+                stats.helium_delivered += amount;
+                entry(&mut stats.players, player).delivered += amount;
+            }
+            GameEvent::HarvesterBuilt { slot } => {
+                stats.harvesters_built += 1;
+                entry(&mut stats.harvesters, slot);
+            }
+            GameEvent::CanisterCrafted { index } => {
+                stats.canisters = stats.canisters.max(index + 1);
+                stats.last_canister_secs = timer.get_single().ok().map(|t| t.timer.elapsed_secs());
+            }
+            GameEvent::StorageFull => {}
+        }
+    }
+}
+
+fn track_time_and_distance(
+    mut stats: ResMut<RunStats>,
+    time: Res<Time>,
+    centers: Query<&HarvesterState, With<Center>>,
+    buggies: Query<&Velocity, With<Buggy>>,
+) {
+    let dt = time.delta_seconds();
+    for state in centers.iter() {
+        match state {
+            HarvesterState::Broken => stats.harvester_secs_broken += dt,
+            HarvesterState::Full => stats.harvester_secs_full += dt,
+            HarvesterState::Work => {}
+        }
+    }
+    for velocity in buggies.iter() {
+        stats.meters_driven += velocity.linvel.length() * dt / PIXELS_PER_METER;
+    }
+}
+
+fn finish_run(
+    mut stats: ResMut<RunStats>,
+    canisters: Res<StoredCanisters>,
+    timer: Query<&EndTimer>,
+) {
+    stats.won = canisters.0 >= CANISTERS_TO_WIN;
+    stats.canisters = canisters.0;
+    stats.run_secs = timer.get_single().map_or(0.0, |t| t.timer.elapsed_secs());
+    if stats.won {
+        stats.time_left_secs = timer.get_single().map_or(0.0, |t| t.timer.remaining_secs());
+    }
+    stats.score = stats.compute_score();
+}
+
+/// Writes the stats next to the game on desktop, or to the browser console on the web
+pub fn export_stats(stats: &RunStats, localization: &Localization) -> String {
+    let json = stats.to_json();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = format!("run_stats_{stamp}.json");
+        match std::fs::write(&path, json) {
+            Ok(()) => localization.format("export-saved", &[("path", path.into())]),
+            Err(e) => {
+                warn!("Can't save run stats: {e}");
+                localization.get("export-failed")
+            }
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        info!("{json}");
+        localization.get("export-printed")
+    }
+}
@@ -0,0 +1,107 @@
+//! Tiny chiptune synthesizer for the sound effects, so they don't need recorded samples
+
+use std::{f32::consts::TAU, sync::Arc};
+
+use kira::{
+    dsp::Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+};
+
+const SAMPLE_RATE: u32 = 44_100;
+
+#[derive(Clone, Copy)]
+pub enum Wave {
+    Sine,
+    Square,
+    Saw,
+    Noise,
+}
+
+/// One voice: a wave sliding from `from_hz` to `to_hz` over `secs`,
+/// with a short attack and a linear fade out unless it's held
+#[derive(Clone, Copy)]
+pub struct Tone {
+    pub wave: Wave,
+    pub from_hz: f32,
+    pub to_hz: f32,
+    pub start: f32,
+    pub secs: f32,
+    pub volume: f32,
+    /// Plays at full volume from the first to the last sample, so a loop made of it doesn't click
+    pub held: bool,
+}
+
+impl Tone {
+    pub fn new(wave: Wave, hz: f32, secs: f32) -> Self {
+        Tone {
+            wave,
+            from_hz: hz,
+            to_hz: hz,
+            start: 0.0,
+            secs,
+            volume: 0.5,
+            held: false,
+        }
+    }
+
+    /// Pads a loop out to `secs`
+    pub fn silence(secs: f32) -> Self {
+        Tone::new(Wave::Sine, 0.0, secs).volume(0.0)
+    }
+
+    pub fn slide_to(self, to_hz: f32) -> Self {
+        Tone { to_hz, ..self }
+    }
+
+    pub fn at(self, start: f32) -> Self {
+        Tone { start, ..self }
+    }
+
+    pub fn volume(self, volume: f32) -> Self {
+        Tone { volume, ..self }
+    }
+
+    pub fn held(self) -> Self {
+        Tone { held: true, ..self }
+    }
+}
+
+/// Sums the tones into a sound
+pub fn render(tones: &[Tone]) -> StaticSoundData {
+    let secs = tones.iter().map(|t| t.start + t.secs).fold(0.0, f32::max);
+    let length = (secs * SAMPLE_RATE as f32) as usize;
+    let mut samples = vec![0.0; length];
+    // Deterministic noise, every run sounds the same
+    let mut seed = 0x2545_f491_u32;
+
+    for tone in tones {
+        let first = (tone.start * SAMPLE_RATE as f32) as usize;
+        let count = (tone.secs * SAMPLE_RATE as f32) as usize;
+        let mut phase = 0.0;
+        for i in 0..count.min(length - first) {
+            let t = i as f32 / count as f32;
+            let hz = tone.from_hz + (tone.to_hz - tone.from_hz) * t;
+            phase = (phase + hz / SAMPLE_RATE as f32).fract();
+            let value = match tone.wave {
+                Wave::Sine => (phase * TAU).sin(),
+                Wave::Square => (0.5 - phase).signum(),
+                Wave::Saw => phase * 2.0 - 1.0,
+                Wave::Noise => {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+                }
+            };
+            let attack = (i as f32 / (0.005 * SAMPLE_RATE as f32)).min(1.0);
+            let envelope = if tone.held { 1.0 } else { attack * (1.0 - t) };
+            samples[first + i] += value * envelope * tone.volume;
+        }
+    }
+
+    StaticSoundData {
+        sample_rate: SAMPLE_RATE,
+        frames: Arc::new(samples.into_iter().map(Frame::from_mono).collect()),
+        settings: StaticSoundSettings::default(),
+    }
+}
@@ -1,18 +1,27 @@
 use crate::{
-    buggy::{buggy_movement_and_control, setup_buggy, Buggy},
+    buggy::{
+        buggy_movement_and_control, frame_buggies, read_local_input, setup_buggy, spin_wheels,
+        update_buggy_tooltips, Buggy, KeyBindings, LocalControl, MAX_PLAYERS,
+    },
+    events::GameEvent,
     harvester::{
-        move_harvesters, BreakTime, Center, HarvesterState, Helium, StorageHelium, StoredCanisters,
-        TotalHarvesters, BREAKTIME, MAX_HELIUM,
+        move_harvesters, BreakTime, Center, HarvesterState, Helium, SlotNumber, StorageHelium,
+        StoredCanisters, TotalHarvesters, BREAKTIME, MAX_HELIUM,
     },
+    i18n::{Localization, Localized},
+    in_game,
+    net::NetRole,
+    picking::{Clickable, Clicked},
+    scaling::UiCamera,
     start::{check_end, set_timer},
     tooltip::{spawn_tooltip, TooltipString},
-    util::{image_from_aseprite, TerrainAssetHandlers},
+    util::{disc_image, TerrainAssetHandlers},
     AppState, CELL_SIZE_TERRAIN, HEIGHT, PIXEL_MULTIPLIER, WIDTH,
 };
-use bevy::{prelude::*, render::camera::RenderTarget, sprite::collide_aabb::collide};
+use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use once_cell::sync::OnceCell;
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 
 pub const COLLECT_DISTANCE: f32 = 300.0;
 pub const TERRAIN_SIZE: (f32, f32) = (440.0 * PIXEL_MULTIPLIER, 320.0 * PIXEL_MULTIPLIER);
@@ -20,6 +29,7 @@ pub const MAX_HELIUM_STORAGE: usize = 20;
 pub const HELIUM_TO_BUILD_HARVESTER: usize = MAX_HELIUM_STORAGE / 2;
 pub const HELIUM_TO_MAKE_CANISTER: usize = MAX_HELIUM_STORAGE;
 pub const CANISTERS_TO_WIN: usize = 5;
+pub const PIXELS_PER_METER: f32 = 12.0;
 
 #[derive(Component)]
 pub struct TerrainMarker;
@@ -33,6 +43,27 @@ pub struct MapButton;
 #[derive(Component)]
 pub struct Base;
 
+/// Everything a player can do to the world, applied by whoever runs the simulation
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum PlayerAction {
+    /// Collect from or repair the center in the given slot
+    UseCenter {
+        player: usize,
+        slot: usize,
+    },
+    UnloadAtBase {
+        player: usize,
+    },
+    /// Use whatever is in reach of the buggy
+    Interact {
+        player: usize,
+    },
+    BuildHarvester {
+        cell: (i8, i8),
+    },
+    MakeCanister,
+}
+
 pub struct TerrainPlugin;
 
 impl Plugin for TerrainPlugin {
@@ -47,44 +78,42 @@ impl Plugin for TerrainPlugin {
         .add_system_set(
             SystemSet::on_update(AppState::Terrain)
                 .with_system(mouse_clicks)
-                .with_system(update_button.after(buggy_movement_and_control))
+                .with_system(interact_keys)
+                .with_system(update_button.after(frame_buggies))
                 .with_system(update_base),
         )
         .add_system_set(SystemSet::on_enter(AppState::Terrain).with_system(enable_terrain_cam))
-        .add_system(move_harvesters)
-        .add_system(check_end)
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(in_game)
+                .with_system(move_harvesters)
+                .with_system(check_end)
+                .with_system(apply_player_actions)
+                .with_system(frame_buggies.after(buggy_movement_and_control)),
+        )
+        .add_system(read_local_input.before(buggy_movement_and_control))
         .add_system(buggy_movement_and_control)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(12.0));
+        .add_system(update_buggy_tooltips)
+        .add_system(spin_wheels)
+        .add_event::<PlayerAction>()
+        .add_event::<GameEvent>()
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(
+            PIXELS_PER_METER,
+        ));
         // .add_plugin(RapierDebugRenderPlugin::default());
     }
 }
 
 fn setup_terrain(
     mut commands: Commands,
-    mut textures: ResMut<Assets<Image>>,
     mut phys: ResMut<RapierConfiguration>,
     terrain_assets: Res<TerrainAssetHandlers>,
+    mut images: ResMut<Assets<Image>>,
 ) {
-    //FIXME filsam: reduce boilerplate
-    static TERRAIN_IMAGE_CELL: OnceCell<Image> = OnceCell::new();
-    static TERRAIN_TEXTURE_HANDLE_CELL: OnceCell<Handle<Image>> = OnceCell::new();
-
-    let terrain_image = TERRAIN_IMAGE_CELL.get_or_init(|| {
-        image_from_aseprite(include_bytes!("../assets/spritemap5.aseprite"), "Layer 1")
-    });
-    let terrain_texture_handle =
-        TERRAIN_TEXTURE_HANDLE_CELL.get_or_init(|| textures.add(terrain_image.clone()));
-    let size = terrain_image.size() * PIXEL_MULTIPLIER;
+    let size = terrain_assets.map.size;
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(size),
-                ..default()
-            },
-            texture: terrain_texture_handle.clone(),
-            ..default()
-        })
+        .spawn(terrain_assets.map.sprite_sheet())
         .insert(TerrainSprite)
         .insert(TerrainMarker);
     commands.spawn((Camera2dBundle::default(), TerrainMarker));
@@ -130,16 +159,11 @@ fn setup_terrain(
     }
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(terrain_assets.map_button[0].1),
-                ..default()
-            },
-            texture: terrain_assets.map_button[0].0.clone(),
-            ..default()
-        })
+        .spawn(terrain_assets.map_button[0].sprite_sheet())
         .insert(MapButton)
-        .insert(TooltipString("Open info panel".to_string()))
+        .insert(Clickable)
+        .insert(TooltipString::default())
+        .insert(Localized("open-panel"))
         .insert(TerrainMarker);
 
     commands
@@ -152,6 +176,8 @@ fn setup_terrain(
                 )),
                 ..default()
             },
+            // Hovers and clicks only count on the round base, not the corners of its cell
+            texture: images.add(disc_image(CELL_SIZE_TERRAIN as u32)),
             transform: Transform {
                 translation: Vec3::new(
                     TERRAIN_SIZE.0 / 2.0 - CELL_SIZE_TERRAIN * PIXEL_MULTIPLIER / 2.0,
@@ -163,7 +189,9 @@ fn setup_terrain(
             ..default()
         })
         .insert(Base)
-        .insert(TooltipString("Base".to_string()))
+        .insert(Clickable)
+        // Filled in by `update_base`
+        .insert(TooltipString::default())
         .insert(TerrainMarker);
 
     commands.insert_resource(TotalHarvesters(0));
@@ -173,153 +201,273 @@ fn setup_terrain(
 
 fn enable_terrain_cam(
     mut cam: Query<&mut Camera, With<TerrainMarker>>,
-    mut panel_cam: Query<&mut Camera, Without<TerrainMarker>>,
+    mut panel_cam: Query<&mut Camera, (Without<TerrainMarker>, Without<UiCamera>)>,
 ) {
     cam.for_each_mut(|mut c| c.is_active = true);
     panel_cam.for_each_mut(|mut c| c.is_active = false);
 }
 
-#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+/// Moves the buggy's helium into the main storage, keeping whatever doesn't fit
+fn unload_at_base(
+    player: usize,
+    buggy_helium: &mut Helium,
+    storage_total: &mut StorageHelium,
+    events: &mut EventWriter<GameEvent>,
+) {
+    let before = storage_total.0;
+    storage_total.0 += buggy_helium.0;
+    buggy_helium.0 = 0;
+    if storage_total.0 > MAX_HELIUM_STORAGE {
+        buggy_helium.0 += storage_total.0 - MAX_HELIUM_STORAGE;
+        storage_total.0 = MAX_HELIUM_STORAGE;
+    }
+    if storage_total.0 > before {
+        events.send(GameEvent::HeliumDelivered {
+            player,
+            amount: storage_total.0 - before,
+        });
+        if storage_total.0 == MAX_HELIUM_STORAGE {
+            events.send(GameEvent::StorageFull);
+        }
+    }
+}
+
+/// Collects helium from a working or full center, or repairs a broken one
+fn interact_with_center(
+    player: usize,
+    slot: usize,
+    buggy_helium: &mut Helium,
+    helium: &mut Helium,
+    state: &mut HarvesterState,
+    breaktime: &mut BreakTime,
+    events: &mut EventWriter<GameEvent>,
+) {
+    match *state {
+        HarvesterState::Work | HarvesterState::Full => {
+            if helium.0 > 0 {
+                events.send(GameEvent::HeliumCollected {
+                    player,
+                    slot,
+                    amount: helium.0,
+                });
+            }
+            buggy_helium.0 += helium.0;
+            helium.0 = 0;
+            *state = HarvesterState::Work;
+        }
+        HarvesterState::Broken => {
+            let mut rng = thread_rng();
+            breaktime.0 = rng.gen_range(BREAKTIME.0..BREAKTIME.1);
+            if helium.0 == MAX_HELIUM {
+                *state = HarvesterState::Full;
+            } else {
+                *state = HarvesterState::Work;
+            }
+            events.send(GameEvent::HarvesterRepaired { player, slot });
+        }
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
 fn mouse_clicks(
-    mut buggy: Query<(&Transform, &mut Helium, &mut TooltipString), (With<Buggy>, Without<Center>)>,
+    buggies: Query<(&Buggy, &Transform), With<LocalControl>>,
+    centers: Query<(&Transform, &SlotNumber), With<Center>>,
+    mut clicks: EventReader<Clicked>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut app_state: ResMut<State<AppState>>,
+    map_button: Query<(), With<MapButton>>,
+    base: Query<&Transform, With<Base>>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    for Clicked(entity) in clicks.iter() {
+        if map_button.contains(*entity) {
+            app_state.set(AppState::Panel).unwrap();
+            buttons.clear();
+            return;
+        }
+
+        if let Ok(base) = base.get(*entity) {
+            if let Some(buggy) = closest_in_reach(&buggies, base) {
+                actions.send(PlayerAction::UnloadAtBase {
+                    player: buggy.player,
+                });
+            }
+            return;
+        }
+
+        let Ok((center, slot)) = centers.get(*entity) else {continue};
+        if let Some(buggy) = closest_in_reach(&buggies, center) {
+            actions.send(PlayerAction::UseCenter {
+                player: buggy.player,
+                slot: slot.0,
+            });
+        }
+    }
+}
+
+/// The local buggy that does the job when something is clicked
+fn closest_in_reach<'a>(
+    buggies: &'a Query<(&Buggy, &Transform), With<LocalControl>>,
+    target: &Transform,
+) -> Option<&'a Buggy> {
+    // The base sits far below the terrain, only the distance on the ground counts
+    let distance = |t: &Transform| {
+        target
+            .translation
+            .truncate()
+            .distance(t.translation.truncate())
+    };
+    buggies
+        .iter()
+        .filter(|(_, t)| distance(t) <= COLLECT_DISTANCE)
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+        .map(|(buggy, _)| buggy)
+}
+
+/// Lets each player collect, repair and unload with their own key,
+/// so the second player doesn't have to share the mouse
+fn interact_keys(
+    buggies: Query<(&Buggy, &LocalControl)>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    for (buggy, control) in buggies.iter() {
+        if keys.just_pressed(bindings.0[control.0.min(MAX_PLAYERS - 1)].interact) {
+            actions.send(PlayerAction::Interact {
+                player: buggy.player,
+            });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_player_actions(
+    mut actions: EventReader<PlayerAction>,
+    mut buggies: Query<(&Buggy, &Transform, &mut Helium), Without<Center>>,
     mut centers: Query<
         (
+            &SlotNumber,
             &Transform,
-            &Sprite,
             &mut Helium,
             &mut HarvesterState,
             &mut BreakTime,
         ),
         (With<Center>, Without<Buggy>),
     >,
-    wnds: Res<Windows>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
-    mut buttons: ResMut<Input<MouseButton>>,
-    mut app_state: ResMut<State<AppState>>,
-    map_button: Query<(&Transform, &Sprite), With<MapButton>>,
-    base: Query<(&Transform, &Sprite), With<Base>>,
+    base: Query<&Transform, (With<Base>, Without<Buggy>, Without<Center>)>,
     mut storage_total: ResMut<StorageHelium>,
+    role: Res<NetRole>,
+    mut events: EventWriter<GameEvent>,
 ) {
-    let (buggy, mut storage, mut buggy_string) = buggy.single_mut();
-    let Some((camera, camera_transform)) = q_camera.iter().find(|(c,_)|c.is_active) else {return};
-
-    let wnd = if let RenderTarget::Window(id) = camera.target {
-        wnds.get(id).unwrap()
-    } else {
-        wnds.get_primary().unwrap()
-    };
-
-    if buttons.just_pressed(MouseButton::Left) {
-        if let Some(screen_pos) = wnd.cursor_position() {
-            let window_size = Vec2::new(wnd.width(), wnd.height());
-            let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
-            let ndc_to_world =
-                camera_transform.compute_matrix() * camera.projection_matrix().inverse();
-            let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
-
-            let (map_button, button_sprite) = map_button.single();
-
-            if collide(
-                map_button.translation,
-                Vec2 {
-                    x: button_sprite.custom_size.unwrap().x,
-                    y: button_sprite.custom_size.unwrap().y,
-                },
-                world_pos,
-                Vec2 { x: 1.0, y: 1.0 },
-            )
-            .is_some()
-            {
-                app_state.set(AppState::Panel).unwrap();
-                buttons.clear();
-                return;
-            }
-
-            let (base, base_sprite) = base.single();
+    if !role.is_authoritative() {
+        actions.clear();
+        return;
+    }
+    for action in actions.iter() {
+        let player = match *action {
+            PlayerAction::UseCenter { player, .. }
+            | PlayerAction::UnloadAtBase { player }
+            | PlayerAction::Interact { player } => player,
+            _ => continue,
+        };
+        let Some((_, buggy_pos, mut buggy_helium)) =
+            buggies.iter_mut().find(|(b, ..)| b.player == player)
+        else {continue};
+        let in_reach = |t: &Transform| {
+            t.translation
+                .truncate()
+                .distance(buggy_pos.translation.truncate())
+                <= COLLECT_DISTANCE
+        };
 
-            if collide(
-                base.translation,
-                Vec2 {
-                    x: base_sprite.custom_size.unwrap().x,
-                    y: base_sprite.custom_size.unwrap().y,
-                },
-                world_pos,
-                Vec2 { x: 1.0, y: 1.0 },
-            )
-            .is_some()
-            {
-                storage_total.0 += storage.0;
-                storage.0 = 0;
-                if storage_total.0 > MAX_HELIUM_STORAGE {
-                    storage.0 += storage_total.0 - MAX_HELIUM_STORAGE;
-                    storage_total.0 = MAX_HELIUM_STORAGE;
+        match *action {
+            PlayerAction::UseCenter { slot, .. } => {
+                if let Some((_, _, mut helium, mut state, mut breaktime)) = centers
+                    .iter_mut()
+                    .find(|(s, t, ..)| s.0 == slot && in_reach(t))
+                {
+                    interact_with_center(
+                        player,
+                        slot,
+                        &mut buggy_helium,
+                        &mut helium,
+                        &mut state,
+                        &mut breaktime,
+                        &mut events,
+                    );
                 }
-                buggy_string.0 = format!("Helium amount: {}", storage.0);
-                return;
             }
-
-            for (center, sprite, mut helium, mut state, mut breaktime) in centers.iter_mut() {
-                if collide(
-                    center.translation,
-                    Vec2 {
-                        x: sprite.custom_size.unwrap().x,
-                        y: sprite.custom_size.unwrap().y,
-                    },
-                    world_pos,
-                    Vec2 { x: 1.0, y: 1.0 },
-                )
-                .is_some()
-                    && center.translation.distance(buggy.translation) <= COLLECT_DISTANCE
+            // Clients send it too, so the reach is checked here
+            PlayerAction::UnloadAtBase { .. } if in_reach(base.single()) => {
+                unload_at_base(player, &mut buggy_helium, &mut storage_total, &mut events);
+            }
+            PlayerAction::Interact { .. } => {
+                if in_reach(base.single()) {
+                    unload_at_base(player, &mut buggy_helium, &mut storage_total, &mut events);
+                } else if let Some((slot, _, mut helium, mut state, mut breaktime)) = centers
+                    .iter_mut()
+                    .filter(|(_, t, ..)| in_reach(t))
+                    .min_by(|(_, a, ..), (_, b, ..)| {
+                        let a = a.translation.distance(buggy_pos.translation);
+                        let b = b.translation.distance(buggy_pos.translation);
+                        a.total_cmp(&b)
+                    })
                 {
-                    match *state {
-                        HarvesterState::Work => {
-                            storage.0 += helium.0;
-                            helium.0 = 0;
-                        }
-                        HarvesterState::Full => {
-                            storage.0 += helium.0;
-                            helium.0 = 0;
-                            *state = HarvesterState::Work;
-                        }
-                        HarvesterState::Broken => {
-                            let mut rng = thread_rng();
-                            breaktime.0 = rng.gen_range(BREAKTIME.0..BREAKTIME.1);
-                            if helium.0 == MAX_HELIUM {
-                                *state = HarvesterState::Full;
-                            } else {
-                                *state = HarvesterState::Work;
-                            }
-                        }
-                    };
-                    buggy_string.0 = format!("Helium amount: {}", storage.0);
+                    interact_with_center(
+                        player,
+                        slot.0,
+                        &mut buggy_helium,
+                        &mut helium,
+                        &mut state,
+                        &mut breaktime,
+                        &mut events,
+                    );
                 }
             }
+            _ => {}
         }
     }
 }
 
 #[allow(clippy::type_complexity)]
 pub fn update_button(
-    camera: Query<&Transform, (With<TerrainMarker>, With<Camera2d>, Without<MapButton>)>,
-    mut button: Query<(&mut Transform, &mut Handle<Image>), With<MapButton>>,
+    camera: Query<
+        (&Transform, &OrthographicProjection),
+        (With<TerrainMarker>, With<Camera2d>, Without<MapButton>),
+    >,
+    mut button: Query<(&mut Transform, &mut TextureAtlasSprite), With<MapButton>>,
     centers: Query<&HarvesterState, With<Center>>,
     terrain_assets: Res<TerrainAssetHandlers>,
 ) {
-    let camera = camera.single().translation;
+    let (camera, projection) = camera.single();
+    let camera = camera.translation;
     let (mut button, mut sprite) = button.single_mut();
-    button.translation.x = camera.x - WIDTH / 2.0 + 40.0;
-    button.translation.y = camera.y + HEIGHT / 2.0 - 50.0;
+    // Stay pinned to the same spot on screen when the camera zooms out
+    button.translation.x = camera.x + (-WIDTH / 2.0 + 40.0) * projection.scale;
+    button.translation.y = camera.y + (HEIGHT / 2.0 - 50.0) * projection.scale;
     button.translation.z = 3.0;
+    button.scale = Vec3::splat(projection.scale);
 
-    *sprite = terrain_assets.map_button[0].0.clone();
+    sprite.index = terrain_assets.map_button[0].index;
     for center in centers.iter() {
         if !matches!(*center, HarvesterState::Work) {
-            *sprite = terrain_assets.map_button[1].0.clone();
+            sprite.index = terrain_assets.map_button[1].index;
         }
     }
 }
 
-fn update_base(mut base: Query<&mut TooltipString, With<Base>>, storage_total: Res<StorageHelium>) {
+fn update_base(
+    mut base: Query<&mut TooltipString, With<Base>>,
+    storage_total: Res<StorageHelium>,
+    localization: Res<Localization>,
+) {
     let mut string = base.single_mut();
-    string.0 = format!("Helium:\n{}/{}", storage_total.0, MAX_HELIUM_STORAGE);
+    *string = TooltipString::text(localization.format(
+        "base-helium",
+        &[
+            ("helium", storage_total.0.into()),
+            ("max", MAX_HELIUM_STORAGE.into()),
+        ],
+    ));
 }
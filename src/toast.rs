@@ -0,0 +1,123 @@
+use super::*;
+
+/// Seconds a toast stays on screen
+const TOAST_TIME: f32 = 3.0;
+/// Older toasts are dropped when more than this are stacked
+const MAX_TOASTS: usize = 4;
+
+/// Short message shown at the top of the screen for a few seconds,
+/// drawn as UI so it shows over whichever camera is active
+pub struct Toast(pub String);
+
+#[derive(Component)]
+struct ToastStack;
+
+#[derive(Component)]
+struct ToastTimer(Timer);
+
+#[derive(Resource)]
+struct ToastFont(Handle<Font>);
+
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Toast>()
+            .add_startup_system(load_toast_font)
+            .add_system(spawn_toasts)
+            .add_system(expire_toasts);
+    }
+}
+
+fn load_toast_font(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+    let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
+    let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
+    commands.insert_resource(ToastFont(font_handle));
+}
+
+fn spawn_toasts(
+    mut commands: Commands,
+    mut toasts: EventReader<Toast>,
+    font: Res<ToastFont>,
+    stack: Query<Entity, With<ToastStack>>,
+    shown: Query<(Entity, &ToastTimer)>,
+) {
+    if toasts.is_empty() {
+        return;
+    }
+    // The finish screen despawns every entity, so the stack is made on demand
+    let stack = stack.get_single().unwrap_or_else(|_| {
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            top: Val::Px(10.0),
+                            left: Val::Px(0.0),
+                            ..default()
+                        },
+                        size: Size::new(Val::Percent(100.0), Val::Auto),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                ToastStack,
+            ))
+            .id()
+    });
+
+    let mut shown: Vec<_> = shown.iter().collect();
+    shown.sort_by(|(_, a), (_, b)| b.0.elapsed_secs().total_cmp(&a.0.elapsed_secs()));
+    let mut count = shown.len();
+    for Toast(message) in toasts.iter() {
+        if count >= MAX_TOASTS {
+            if let Some((oldest, _)) = shown.first() {
+                commands.entity(*oldest).despawn_recursive();
+                shown.remove(0);
+                count -= 1;
+            }
+        }
+        let toast = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(6.0)),
+                        margin: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                    ..default()
+                },
+                ToastTimer(Timer::from_seconds(TOAST_TIME, TimerMode::Once)),
+            ))
+            .with_children(|toast| {
+                toast.spawn(TextBundle::from_section(
+                    message.clone(),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 12.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            })
+            .id();
+        commands.entity(stack).add_child(toast);
+        count += 1;
+    }
+}
+
+fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut ToastTimer)>,
+) {
+    for (entity, mut timer) in toasts.iter_mut() {
+        if timer.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
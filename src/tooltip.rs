@@ -1,76 +1,281 @@
-use bevy::{render::camera::RenderTarget, sprite::collide_aabb::collide};
+use bevy::{
+    sprite::Anchor,
+    text::{update_text2d_layout, Text2dSize},
+    transform::TransformSystem,
+};
 
-use crate::util::get_cursor_pos_in_world_coord;
+use crate::{
+    atlas::{Atlas, Region},
+    picking::{Cursor, Hovered},
+    scaling::GameCameras,
+    settings::GameplaySettings,
+};
 
 use super::*;
 
+/// Space around the text and between the icon and the text
+const TOOLTIP_PADDING: f32 = 1.0 * PIXEL_MULTIPLIER;
+/// From the cursor to the corner of the tooltip
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(10.0, -10.0);
+/// Above everything on the terrain and the panel
+const TOOLTIP_Z: f32 = 50.0;
+
+/// The corner the background, icon and text hang from
 #[derive(Component)]
 pub struct Tooltip;
 
 #[derive(Component)]
-pub struct TooltipString(pub String);
+struct TooltipBackground;
+
+#[derive(Component)]
+struct TooltipIcon;
+
+#[derive(Component)]
+struct TooltipText;
+
+#[derive(Resource)]
+struct TooltipFont(Handle<Font>);
 
-pub fn spawn_tooltip(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+/// What the tooltip of an entity says, every section in its own color with an optional icon in front
+#[derive(Component, Default, Clone)]
+pub struct TooltipString {
+    pub sections: Vec<(String, Color)>,
+    pub icon: Option<(Region, Color)>,
+}
+
+impl TooltipString {
+    pub fn text(text: String) -> Self {
+        TooltipString {
+            sections: vec![(text, Color::WHITE)],
+            icon: None,
+        }
+    }
+
+    /// The sections follow each other, new lines have to be in the text
+    pub fn with(mut self, text: String, color: Color) -> Self {
+        self.sections.push((text, color));
+        self
+    }
+
+    pub fn with_icon(mut self, icon: &Region, color: Color) -> Self {
+        self.icon = Some((icon.clone(), color));
+        self
+    }
+}
+
+pub struct TooltipPlugin;
+
+impl Plugin for TooltipPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_tooltip).add_system_to_stage(
+            CoreStage::PostUpdate,
+            // The text is measured in the same stage
+            layout_tooltip
+                .after(update_text2d_layout)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+pub fn spawn_tooltip(mut commands: Commands, mut fonts: ResMut<Assets<Font>>, atlas: Res<Atlas>) {
     let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
     let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
+    commands.insert_resource(TooltipFont(font_handle));
 
-    let text_style = TextStyle {
-        font: font_handle,
-        font_size: 15.0,
-        color: Color::WHITE,
-    };
-    let text_alignment = TextAlignment::TOP_LEFT;
-
-    commands.spawn((
-        Text2dBundle {
-            text: Text::from_section("translation", text_style).with_alignment(text_alignment),
-            ..default()
-        },
-        Tooltip,
-    ));
+    commands
+        .spawn((
+            SpatialBundle {
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            Tooltip,
+        ))
+        .with_children(|tooltip| {
+            tooltip.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0.0, 0.0, 0.0, 0.8),
+                        anchor: Anchor::TopLeft,
+                        ..default()
+                    },
+                    ..default()
+                },
+                TooltipBackground,
+            ));
+            tooltip.spawn((
+                SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        anchor: Anchor::TopLeft,
+                        ..default()
+                    },
+                    texture_atlas: atlas.layout.clone(),
+                    ..default()
+                },
+                TooltipIcon,
+            ));
+            tooltip.spawn((
+                Text2dBundle {
+                    text: Text {
+                        sections: vec![],
+                        alignment: TextAlignment::TOP_LEFT,
+                    },
+                    ..default()
+                },
+                TooltipText,
+            ));
+        });
+}
+
+/// Whatever the cursor rested on last and for how long
+#[derive(Default)]
+pub struct Hover {
+    object: Option<Entity>,
+    secs: f32,
 }
 
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_tooltip(
+    mut tooltip: Query<&mut Visibility, With<Tooltip>>,
+    mut text: Query<&mut Text, With<TooltipText>>,
+    mut icon: Query<
+        (&mut TextureAtlasSprite, &mut Visibility),
+        (With<TooltipIcon>, Without<Tooltip>),
+    >,
+    font: Option<Res<TooltipFont>>,
+    mut hovers: EventReader<Hovered>,
+    objects: Query<&TooltipString>,
+    time: Res<Time>,
+    gameplay: Res<GameplaySettings>,
+    mut hover: Local<Hover>,
+) {
+    let (Ok(mut visibility), Ok(mut text), Ok((mut icon, mut icon_visibility)), Some(font)) = (
+        tooltip.get_single_mut(),
+        text.get_single_mut(),
+        icon.get_single_mut(),
+        font,
+    ) else {return};
+
+    // Picking sends the topmost entity only
+    let hovered = hovers
+        .iter()
+        .last()
+        .and_then(|Hovered(entity)| Some((*entity, objects.get(*entity).ok()?)));
+
+    if hovered.map(|(entity, _)| entity) != hover.object {
+        hover.object = hovered.map(|(entity, _)| entity);
+        hover.secs = 0.0;
+    }
+    hover.secs += time.delta_seconds();
+
+    let shown = hovered
+        .map(|(_, string)| string)
+        .filter(|string| !string.sections.is_empty() && hover.secs >= gameplay.tooltip_delay);
+    visibility.is_visible = shown.is_some();
+    let Some(string) = shown else {return};
+
+    text.sections = string
+        .sections
+        .iter()
+        .map(|(value, color)| {
+            TextSection::new(
+                value.clone(),
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 15.0,
+                    color: *color,
+                },
+            )
+        })
+        .collect();
+    icon_visibility.is_visible = string.icon.is_some();
+    if let Some((region, color)) = &string.icon {
+        icon.index = region.index;
+        icon.custom_size = Some(region.size);
+        icon.color = *color;
+    }
+}
+
+/// Sizes the background to the text, keeps the tooltip the same size on screen
+/// whatever the zoom and inside the view, flipping it to the other side of the cursor
 #[allow(clippy::type_complexity)]
-pub fn update_tooltip(
-    mut tooltip: Query<(&mut Transform, &mut Text), (With<Tooltip>, Without<TooltipString>)>,
-    wnds: Res<Windows>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
-    objects: Query<(&Transform, &Sprite, &TooltipString), With<TooltipString>>,
+fn layout_tooltip(
+    mut tooltip: Query<(&mut Transform, &Visibility), With<Tooltip>>,
+    mut background: Query<&mut Sprite, With<TooltipBackground>>,
+    mut icon: Query<
+        (&mut Transform, &TextureAtlasSprite, &Visibility),
+        (With<TooltipIcon>, Without<Tooltip>),
+    >,
+    mut text: Query<
+        (&mut Transform, &Text2dSize),
+        (With<TooltipText>, Without<Tooltip>, Without<TooltipIcon>),
+    >,
+    cursor: Res<Cursor>,
+    q_camera: GameCameras,
 ) {
-    if tooltip.is_empty() {
+    let (
+        Ok((mut transform, visibility)),
+        Ok(mut background),
+        Ok((mut icon_transform, icon, icon_visibility)),
+        Ok((mut text_transform, text_size)),
+    ) = (
+        tooltip.get_single_mut(),
+        background.get_single_mut(),
+        icon.get_single_mut(),
+        text.get_single_mut(),
+    )
+    else {return};
+    let Some(cursor) = cursor.0 else {return};
+    if !visibility.is_visible {
         return;
     }
-    let (mut tooltip, mut text) = tooltip.single_mut();
-
-    let Some((camera, camera_transform)) = q_camera.iter().find(|(c,_)|c.is_active) else {return};
 
-    let wnd = if let RenderTarget::Window(id) = camera.target {
-        wnds.get(id).unwrap()
+    let icon_size = match (icon_visibility.is_visible, icon.custom_size) {
+        (true, Some(size)) => size,
+        _ => Vec2::ZERO,
+    };
+    let gap = if icon_size == Vec2::ZERO {
+        0.0
     } else {
-        wnds.get_primary().unwrap()
+        TOOLTIP_PADDING
     };
+    text_transform.translation =
+        Vec3::new(TOOLTIP_PADDING + icon_size.x + gap, -TOOLTIP_PADDING, 2.0);
+    let inner = Vec2::new(
+        icon_size.x + gap + text_size.size.x,
+        icon_size.y.max(text_size.size.y),
+    );
+    // The icon is centered on the text
+    icon_transform.translation = Vec3::new(
+        TOOLTIP_PADDING,
+        -TOOLTIP_PADDING - (inner.y - icon_size.y) / 2.0,
+        1.0,
+    );
+    let size = inner + 2.0 * TOOLTIP_PADDING;
+    background.custom_size = Some(size);
 
-    text.sections[0].value = "".to_string();
-
-    if let Some(world_pos) = get_cursor_pos_in_world_coord(wnd, camera_transform, camera) {
-        tooltip.translation.x = world_pos.x + 10.0;
-        tooltip.translation.y = world_pos.y - 10.0;
-        tooltip.translation.z = 5.0;
+    // The world corners of the view of the active camera
+    let Some((camera, camera_transform)) = q_camera.iter().find(|(c,_)|c.is_active) else {return};
+    let (Some(min), Some(max)) = (
+        camera.viewport_to_world(camera_transform, Vec2::ZERO),
+        camera
+            .logical_viewport_size()
+            .and_then(|viewport| camera.viewport_to_world(camera_transform, viewport)),
+    ) else {return};
+    let (min, max) = (min.origin.truncate(), max.origin.truncate());
+    let zoom = (max.y - min.y) / HEIGHT;
+    let size = size * zoom;
 
-        for (object, sprite, string) in objects.iter() {
-            if collide(
-                object.translation,
-                Vec2 {
-                    x: sprite.custom_size.unwrap().x,
-                    y: sprite.custom_size.unwrap().y,
-                },
-                world_pos.extend(0.0),
-                Vec2 { x: 1.0, y: 1.0 },
-            )
-            .is_some()
-            {
-                text.sections[0].value = string.0.clone();
-            }
-        }
+    let mut corner = cursor + TOOLTIP_OFFSET * zoom;
+    if corner.x + size.x > max.x {
+        corner.x = cursor.x - TOOLTIP_OFFSET.x * zoom - size.x;
+    }
+    if corner.y - size.y < min.y {
+        corner.y = cursor.y - TOOLTIP_OFFSET.y * zoom + size.y;
     }
+    let corner = corner.clamp(
+        Vec2::new(min.x, min.y + size.y),
+        Vec2::new(max.x - size.x, max.y).max(Vec2::new(min.x, min.y + size.y)),
+    );
+    transform.translation = corner.extend(TOOLTIP_Z);
+    transform.scale = Vec3::new(zoom, zoom, 1.0);
 }
@@ -0,0 +1,404 @@
+//! A run that walks a new player through the game one step at a time. The ship waits
+//! until the last step is done, the step's sprite is framed and a hint says what to do.
+
+use crate::{
+    buggy::{Buggy, KeyBindings, LocalControl},
+    events::GameEvent,
+    harvester::{Center, TotalHarvesters},
+    i18n::Localization,
+    net::NetRole,
+    panel::{
+        place_harvester, BuildHarvesterButtonSensor, CanisterButtonSensor, SlotSprites,
+        SwitchToTerrainButton,
+    },
+    settings::key_name,
+    start::{check_end, EndTimer},
+    terrain::{
+        Base, MapButton, COLLECT_DISTANCE, HELIUM_TO_BUILD_HARVESTER, HELIUM_TO_MAKE_CANISTER,
+    },
+    toast::Toast,
+    util::{PanelAssetHandlers, TerrainAssetHandlers},
+};
+
+use super::*;
+
+const TUTORIAL_KEY: KeyCode = KeyCode::T;
+/// A short drive to the left of where the buggy starts
+const TUTORIAL_HARVESTER_CELL: (i8, i8) = (6, 4);
+/// Over the sprites on both screens, under the tooltip
+const HIGHLIGHT_Z: f32 = 40.0;
+const HIGHLIGHT_THICKNESS: f32 = 1.0 * PIXEL_MULTIPLIER;
+/// Blinks per second of the frame
+const HIGHLIGHT_SPEED: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    DriveToHarvester,
+    Collect,
+    Unload,
+    BuildHarvester,
+    CraftCanister,
+}
+
+impl Step {
+    fn next(self) -> Option<Step> {
+        match self {
+            Step::DriveToHarvester => Some(Step::Collect),
+            Step::Collect => Some(Step::Unload),
+            Step::Unload => Some(Step::BuildHarvester),
+            Step::BuildHarvester => Some(Step::CraftCanister),
+            Step::CraftCanister => None,
+        }
+    }
+
+    fn hint_id(self) -> &'static str {
+        match self {
+            Step::DriveToHarvester => "tutorial-drive",
+            Step::Collect => "tutorial-collect",
+            Step::Unload => "tutorial-unload",
+            Step::BuildHarvester => "tutorial-build",
+            Step::CraftCanister => "tutorial-craft",
+        }
+    }
+
+    /// Whether the step is done on the panel rather than on the terrain
+    fn on_panel(self) -> bool {
+        matches!(self, Step::BuildHarvester | Step::CraftCanister)
+    }
+}
+
+/// Present while the tutorial runs, the run carries on as usual once it's removed
+#[derive(Resource)]
+struct Tutorial {
+    step: Step,
+}
+
+/// Present for the whole run the tutorial started, it doesn't go into the high scores
+/// or unlock achievements, the free harvester and the held ship would make it too easy
+#[derive(Resource)]
+pub struct TutorialRun;
+
+#[derive(Component)]
+struct TutorialHint;
+
+#[derive(Component)]
+struct TutorialHintText;
+
+/// Frame around the sprite of the current step, its children are the edges
+#[derive(Component)]
+struct TutorialHighlight;
+
+#[derive(Component)]
+struct HighlightEdge;
+
+#[derive(Resource)]
+struct TutorialFont(Handle<Font>);
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_tutorial_font)
+            .add_system_set(SystemSet::on_update(AppState::Start).with_system(start_tutorial))
+            // Restarting or quitting from the pause menu goes through the start screen
+            .add_system_set(SystemSet::on_enter(AppState::Start).with_system(stop_tutorial))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(in_game)
+                    .with_system(place_tutorial_harvester)
+                    .with_system(advance_tutorial)
+                    .with_system(hold_ship.after(advance_tutorial).before(check_end)),
+            )
+            .add_system(show_hint.after(advance_tutorial))
+            .add_system(show_highlight.after(advance_tutorial));
+    }
+}
+
+fn load_tutorial_font(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+    let font = include_bytes!("../assets/PublicPixel-z84yD.ttf");
+    let font_handle = fonts.add(Font::try_from_bytes(font.to_vec()).expect("valid font"));
+    commands.insert_resource(TutorialFont(font_handle));
+}
+
+fn start_tutorial(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    role: Res<NetRole>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // Everyone else would be waiting on one player's lessons
+    if !keys.just_pressed(TUTORIAL_KEY) || *role != NetRole::Offline {
+        return;
+    }
+    commands.insert_resource(Tutorial {
+        step: Step::DriveToHarvester,
+    });
+    commands.insert_resource(TutorialRun);
+    app_state.set(AppState::Terrain).unwrap();
+}
+
+fn stop_tutorial(mut commands: Commands) {
+    commands.remove_resource::<Tutorial>();
+    commands.remove_resource::<TutorialRun>();
+}
+
+/// Runs start without harvesters, the tutorial gives one for free
+fn place_tutorial_harvester(
+    mut commands: Commands,
+    tutorial: Option<Res<Tutorial>>,
+    harvesters: Option<ResMut<TotalHarvesters>>,
+    terrain_assets: Res<TerrainAssetHandlers>,
+    panel_assets: Res<PanelAssetHandlers>,
+    mut slot_sprites: SlotSprites,
+) {
+    let (Some(tutorial), Some(mut harvesters)) = (tutorial, harvesters) else {return};
+    // The slots are spawned together with the terrain
+    if tutorial.step != Step::DriveToHarvester || harvesters.0 > 0 || slot_sprites.is_empty() {
+        return;
+    }
+    place_harvester(
+        &mut commands,
+        &terrain_assets,
+        &panel_assets,
+        &mut slot_sprites,
+        &mut harvesters,
+        TUTORIAL_HARVESTER_CELL,
+    );
+}
+
+fn advance_tutorial(
+    mut commands: Commands,
+    tutorial: Option<ResMut<Tutorial>>,
+    mut events: EventReader<GameEvent>,
+    buggies: Query<&Transform, (With<Buggy>, With<LocalControl>)>,
+    centers: Query<&Transform, With<Center>>,
+    localization: Res<Localization>,
+    mut toasts: EventWriter<Toast>,
+) {
+    let Some(mut tutorial) = tutorial else {
+        events.clear();
+        return;
+    };
+    let in_reach = buggies.iter().any(|buggy| {
+        centers.iter().any(|center| {
+            center
+                .translation
+                .truncate()
+                .distance(buggy.translation.truncate())
+                <= COLLECT_DISTANCE
+        })
+    });
+    let mut done = tutorial.step == Step::DriveToHarvester && in_reach;
+    for event in events.iter() {
+        done |= matches!(
+            (tutorial.step, event),
+            (Step::Collect, GameEvent::HeliumCollected { .. })
+                | (Step::Unload, GameEvent::HeliumDelivered { .. })
+                | (Step::BuildHarvester, GameEvent::HarvesterBuilt { .. })
+                | (Step::CraftCanister, GameEvent::CanisterCrafted { .. })
+        );
+    }
+    if !done {
+        return;
+    }
+    match tutorial.step.next() {
+        Some(step) => tutorial.step = step,
+        None => {
+            commands.remove_resource::<Tutorial>();
+            toasts.send(Toast(localization.get("tutorial-done")));
+        }
+    }
+}
+
+/// The ship doesn't come closer while there's something left to learn
+fn hold_ship(tutorial: Option<Res<Tutorial>>, mut timer: Query<&mut EndTimer>) {
+    let Ok(mut timer) = timer.get_single_mut() else {return};
+    match (tutorial.is_some(), timer.timer.paused()) {
+        (true, false) => timer.timer.pause(),
+        (false, true) => timer.timer.unpause(),
+        _ => (),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_hint(
+    mut commands: Commands,
+    tutorial: Option<Res<Tutorial>>,
+    state: Res<State<AppState>>,
+    localization: Res<Localization>,
+    bindings: Res<KeyBindings>,
+    font: Res<TutorialFont>,
+    mut hint: Query<(Entity, &mut Visibility), With<TutorialHint>>,
+    mut hint_text: Query<&mut Text, With<TutorialHintText>>,
+) {
+    let Some(tutorial) = tutorial else {
+        hint.for_each(|(e, ..)| commands.entity(e).despawn_recursive());
+        return;
+    };
+    let keys = &bindings.0[0];
+    let text = localization.format(
+        tutorial.step.hint_id(),
+        &[
+            (
+                "keys",
+                [keys.forward, keys.left, keys.back, keys.right]
+                    .map(key_name)
+                    .join("/")
+                    .into(),
+            ),
+            ("interact", key_name(keys.interact).into()),
+            ("build", HELIUM_TO_BUILD_HARVESTER.into()),
+            ("craft", HELIUM_TO_MAKE_CANISTER.into()),
+        ],
+    );
+
+    // The finish screen despawns every entity, so the hint is made on demand
+    let (Ok((_, mut visibility)), Ok(mut hint_text)) =
+        (hint.get_single_mut(), hint_text.get_single_mut())
+    else {
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Px(10.0),
+                            left: Val::Px(10.0),
+                            ..default()
+                        },
+                        padding: UiRect::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                TutorialHint,
+            ))
+            .with_children(|hint| {
+                hint.spawn((
+                    TextBundle::from_section(
+                        text,
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: 12.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    TutorialHintText,
+                ));
+            });
+        return;
+    };
+    if hint_text.sections[0].value != text {
+        hint_text.sections[0].value = text;
+    }
+    // Out of the way of the pause menu and the settings
+    let shown = matches!(state.current(), AppState::Terrain | AppState::Panel);
+    if visibility.is_visible != shown {
+        visibility.is_visible = shown;
+    }
+}
+
+/// Frames the sprite of the step, or the button to the screen it's on
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn show_highlight(
+    mut commands: Commands,
+    tutorial: Option<Res<Tutorial>>,
+    state: Res<State<AppState>>,
+    time: Res<Time>,
+    targets: Query<
+        (
+            &GlobalTransform,
+            Option<&Sprite>,
+            Option<&TextureAtlasSprite>,
+        ),
+        Without<HighlightEdge>,
+    >,
+    centers: Query<Entity, With<Center>>,
+    base: Query<Entity, With<Base>>,
+    map_button: Query<Entity, With<MapButton>>,
+    terrain_button: Query<Entity, With<SwitchToTerrainButton>>,
+    harvester_button: Query<Entity, With<BuildHarvesterButtonSensor>>,
+    canister_button: Query<Entity, With<CanisterButtonSensor>>,
+    mut highlight: Query<
+        (Entity, &mut Transform, &mut Visibility, &Children),
+        With<TutorialHighlight>,
+    >,
+    mut edges: Query<
+        (&mut Transform, &mut Sprite),
+        (With<HighlightEdge>, Without<TutorialHighlight>),
+    >,
+) {
+    let Some(tutorial) = tutorial else {
+        highlight.for_each(|(e, ..)| commands.entity(e).despawn_recursive());
+        return;
+    };
+    let Ok((_, mut transform, mut visibility, children)) = highlight.get_single_mut() else {
+        commands
+            .spawn((SpatialBundle::default(), TutorialHighlight))
+            .with_children(|highlight| {
+                for _ in 0..4 {
+                    highlight.spawn((SpriteBundle::default(), HighlightEdge));
+                }
+            });
+        return;
+    };
+
+    let screen = state.current();
+    let target = match (tutorial.step, screen) {
+        (step, AppState::Terrain) if step.on_panel() => map_button.get_single().ok(),
+        (step, AppState::Panel) if !step.on_panel() => terrain_button.get_single().ok(),
+        (Step::DriveToHarvester | Step::Collect, _) => centers.iter().next(),
+        (Step::Unload, _) => base.get_single().ok(),
+        (Step::BuildHarvester, _) => harvester_button.get_single().ok(),
+        (Step::CraftCanister, _) => canister_button.get_single().ok(),
+    };
+    let target = target.and_then(|target| targets.get(target).ok());
+    let playing = matches!(screen, AppState::Terrain | AppState::Panel);
+    let Some((target, sprite, atlas_sprite)) = target.filter(|_| playing) else {
+        visibility.is_visible = false;
+        return;
+    };
+    let Some(size) = sprite
+        .and_then(|s| s.custom_size)
+        .or(atlas_sprite.and_then(|s| s.custom_size))
+    else {
+        visibility.is_visible = false;
+        return;
+    };
+
+    // The map button grows with the zoom of the terrain camera, so does its frame
+    let (scale, _, translation) = target.to_scale_rotation_translation();
+    visibility.is_visible = true;
+    transform.translation = translation.truncate().extend(HIGHLIGHT_Z);
+    transform.scale = scale;
+
+    let blink = (time.elapsed_seconds() * HIGHLIGHT_SPEED * std::f32::consts::TAU).sin();
+    let color = Color::rgba(1.0, 0.9, 0.2, 0.6 + 0.4 * blink);
+    let (half, thickness) = (size / 2.0 + HIGHLIGHT_THICKNESS, HIGHLIGHT_THICKNESS);
+    let sides = [
+        (
+            Vec2::new(0.0, half.y - thickness / 2.0),
+            Vec2::new(half.x * 2.0, thickness),
+        ),
+        (
+            Vec2::new(0.0, -half.y + thickness / 2.0),
+            Vec2::new(half.x * 2.0, thickness),
+        ),
+        (
+            Vec2::new(-half.x + thickness / 2.0, 0.0),
+            Vec2::new(thickness, half.y * 2.0),
+        ),
+        (
+            Vec2::new(half.x - thickness / 2.0, 0.0),
+            Vec2::new(thickness, half.y * 2.0),
+        ),
+    ];
+    for (child, (center, side)) in children.iter().zip(sides) {
+        let Ok((mut edge, mut sprite)) = edges.get_mut(*child) else {continue};
+        edge.translation = center.extend(0.0);
+        sprite.custom_size = Some(side);
+        sprite.color = color;
+    }
+}
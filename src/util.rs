@@ -1,40 +1,13 @@
 use super::*;
-use bevy::render::{render_resource::SamplerDescriptor, texture::ImageSampler};
-use image::{DynamicImage, ImageBuffer};
+use crate::{
+    atlas::{Animation, Art, Atlas, Region, Slice},
+    synth::{render, Tone, Wave},
+};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
 use std::io::Cursor;
 
-pub fn image_from_aseprite(ase_bytes: &[u8], layer_name: &str) -> Image {
-    image_from_aseprite_layer_name_frame(ase_bytes, layer_name, 0)
-}
-
-pub fn image_from_aseprite_layer_name_frame(
-    ase_bytes: &[u8],
-    layer_name: &str,
-    frame: u32,
-) -> Image {
-    let image = asefile::AsepriteFile::read(Cursor::new(ase_bytes))
-        .expect("valid aseprite")
-        .layer_by_name(layer_name)
-        .expect("layer name in the aseprite file")
-        .frame(frame)
-        .image();
-
-    bevy_image_from_ase_image(image)
-}
-
-pub fn bevy_image_from_ase_image(image: old_image::RgbaImage) -> Image {
-    let img_buf = ImageBuffer::from_raw(image.width(), image.height(), image.into_raw())
-        .expect("size of containers to match");
-    let mut image = Image::from_dynamic(DynamicImage::ImageRgba8(img_buf), true);
-    // Disable texture filtering
-    image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
-        mag_filter: bevy::render::render_resource::FilterMode::Nearest,
-        min_filter: bevy::render::render_resource::FilterMode::Nearest,
-        ..default()
-    });
-    image
-}
-
+/// `None` while the cursor is over the bars around the picture
 pub fn get_cursor_pos_in_world_coord(
     wnd: &Window,
     camera_transform: &GlobalTransform,
@@ -42,156 +15,359 @@ pub fn get_cursor_pos_in_world_coord(
 ) -> Option<Vec2> {
     let screen_pos = wnd.cursor_position()?;
 
-    let window_size = Vec2::new(wnd.width(), wnd.height());
-    let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
-    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
-    let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
-    Some(world_pos.truncate())
+    // The cursor counts from the bottom of the window and the viewport from the top
+    let (min, max) = camera.logical_viewport_rect()?;
+    let viewport_pos = Vec2::new(screen_pos.x - min.x, screen_pos.y - (wnd.height() - max.y));
+    if viewport_pos.cmplt(Vec2::ZERO).any() || viewport_pos.cmpgt(max - min).any() {
+        return None;
+    }
+    let ray = camera.viewport_to_world(camera_transform, viewport_pos)?;
+    Some(ray.origin.truncate())
 }
 
-pub type ImgHWithSize = (Handle<Image>, Vec2);
+/// White disc filling a square, transparent around it, for sensors over round things in the art
+pub fn disc_image(diameter: u32) -> Image {
+    let radius = diameter as f32 / 2.0;
+    let data = (0..diameter * diameter)
+        .flat_map(|i| {
+            let pixel = Vec2::new((i % diameter) as f32, (i / diameter) as f32) + 0.5;
+            let alpha = if pixel.distance(Vec2::splat(radius)) <= radius {
+                255
+            } else {
+                0
+            };
+            [255, 255, 255, alpha]
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width: diameter,
+            height: diameter,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
 
 #[derive(Resource)]
 pub struct TerrainAssetHandlers {
     // 0 - red, 1 - yellow, 2 - green
-    pub center_terrain_lamps: [ImgHWithSize; 3],
-    pub harvester: ImgHWithSize,
-    pub center: ImgHWithSize,
+    pub center_terrain_lamps: [Region; 3],
+    pub harvester: Region,
+    pub center: Region,
     // 0 - green, 1 - red
-    pub map_button: [ImgHWithSize; 2],
+    pub map_button: [Region; 2],
+    pub buggy: Region,
+    pub map: Region,
+    /// The wheels of the buggy turn as it drives
+    pub wheels: Animation,
+    pub drill: Animation,
+    /// Lamps of the center: 0 - red blinking, 1 - yellow and 2 - green switching on once
+    pub lamps: [Animation; 3],
 }
 
 #[derive(Resource)]
 pub struct PanelAssetHandlers {
+    pub main: Region,
     /// green, yellow, red
-    pub center_icon: [ImgHWithSize; 3],
-    pub buggy_icon: ImgHWithSize,
-    pub harv_icon: ImgHWithSize,
-    pub ship: ImgHWithSize,
+    pub center_icon: [Region; 3],
+    /// green, yellow, red, each with all its frames
+    pub center_icon_frames: [Animation; 3],
+    pub buggy_icon: Region,
+    pub harv_icon: Region,
+    pub ship: Region,
     /// 3 frames animation
-    pub space: [ImgHWithSize; 3],
+    pub space: [Region; 3],
+    /// The frames of `space` played one after another
+    pub stars: Animation,
     /// 6 slots, each has 0 - not set, 1 - green, 2 - yellow, 3 - red
-    pub harv_slots: [[ImgHWithSize; 4]; 6],
-    pub exit: ImgHWithSize,
+    pub harv_slots: [[Region; 4]; 6],
+    pub exit: Region,
     /// 0 - button, 1 - writing gray, 2 - writing green
-    pub harvester_button: [ImgHWithSize; 3],
+    pub harvester_button: [Region; 3],
     /// 0 - button, 1 - writing gray, 2 - writing green
-    pub tank_button: [ImgHWithSize; 3],
-    pub helium_level: ImgHWithSize,
+    pub tank_button: [Region; 3],
     /// 5 tanks
-    pub tanks: [ImgHWithSize; 5],
+    pub tanks: [Region; 5],
+    /// Shapes of the states for the other palettes: 0 - working, 1 - full, 2 - broken
+    pub state_marks: [Region; 3],
+    pub layout: PanelLayout,
 }
 
-pub fn img_handle_and_size_from_bytes(
-    b: &[u8],
-    layer_name: &str,
-    textures: &mut ResMut<Assets<Image>>,
-) -> ImgHWithSize {
-    let img = image_from_aseprite_layer_name_frame(b, layer_name, 0);
-    let size = img.size();
-    (textures.add(img), size * PIXEL_MULTIPLIER)
+/// Places on the panel, marked with slices in its file
+pub struct PanelLayout {
+    /// Hit regions of the buttons
+    pub exit: Slice,
+    pub harvester: Slice,
+    pub tank: Slice,
+    /// Filled from the bottom as helium is stored
+    pub gauge: Slice,
+    /// The terrain in small, split into cells of `CELL_SIZE_PANEL`
+    pub map: Slice,
+    /// Where the harvester slots are, for the state marks
+    pub slots: [Slice; 6],
 }
 
-pub fn load_assets(mut commands: Commands, mut textures: ResMut<Assets<Image>>) {
-    let center_terrain_lamps_bytes = include_bytes!("../assets/spritecenter1.aseprite");
-    let button_bytes = include_bytes!("../assets/spritebutton2.aseprite");
+#[derive(Resource)]
+pub struct EndingAssetHandlers {
+    pub win: Region,
+    pub lose: Region,
+}
 
-    commands.insert_resource(TerrainAssetHandlers {
-        center_terrain_lamps: ["red", "yellow", "green"].map(|layer_name| {
-            img_handle_and_size_from_bytes(center_terrain_lamps_bytes, layer_name, &mut textures)
-        }),
-        harvester: img_handle_and_size_from_bytes(
-            include_bytes!("../assets/spriteharvester1.aseprite"),
-            "Layer 1",
-            &mut textures,
-        ),
-        center: img_handle_and_size_from_bytes(
-            include_bytes!("../assets/spritecenter1.aseprite"),
-            "base",
-            &mut textures,
+#[derive(Resource)]
+pub struct SoundAssetHandlers {
+    /// Loops, pitched up with the buggy speed
+    pub engine: Handle<AudioSource>,
+    pub brake: Handle<AudioSource>,
+    pub collision: Handle<AudioSource>,
+    pub pickup: Handle<AudioSource>,
+    pub unload: Handle<AudioSource>,
+    pub repair: Handle<AudioSource>,
+    pub alarm: Handle<AudioSource>,
+    pub canister: Handle<AudioSource>,
+    pub click: Handle<AudioSource>,
+    /// Loops played at every center, panned towards it
+    pub center_hum: Handle<AudioSource>,
+    pub center_full: Handle<AudioSource>,
+    pub center_alarm: Handle<AudioSource>,
+}
+
+#[derive(Resource)]
+pub struct MusicAssetHandlers {
+    /// Loops on the start screen
+    pub title: Handle<AudioSource>,
+    /// Loops during the run
+    pub theme: Handle<AudioSource>,
+    /// Layered over the theme as the time runs out
+    pub pressure: Handle<AudioSource>,
+    /// Layered over the theme while harvesters are broken
+    pub trouble: Handle<AudioSource>,
+    /// Played once when the ship takes the player
+    pub win: Handle<AudioSource>,
+    /// Played once when the ship flies by
+    pub lose: Handle<AudioSource>,
+    /// Loops on the finish screen
+    pub outro: Handle<AudioSource>,
+}
+
+pub fn load_assets(mut commands: Commands, atlas: Res<Atlas>) {
+    let (terrain, panel, endings) = sprites(&*atlas);
+    commands.insert_resource(terrain);
+    commands.insert_resource(panel);
+    commands.insert_resource(endings);
+}
+
+/// Every sprite and slice the game draws, `--check-assets` goes through them too
+pub fn sprites(
+    art: &impl Art,
+) -> (
+    TerrainAssetHandlers,
+    PanelAssetHandlers,
+    EndingAssetHandlers,
+) {
+    let image = |file, layer_name: &str| art.region(&format!("{file}#{layer_name}"));
+
+    let terrain = TerrainAssetHandlers {
+        center_terrain_lamps: ["red", "yellow", "green"]
+            .map(|layer_name| image("spritecenter1.aseprite", layer_name)),
+        harvester: image("spriteharvester1.aseprite", "Layer 1"),
+        center: image("spritecenter1.aseprite", "base"),
+        map_button: ["green", "red"].map(|layer_name| image("spritebutton2.aseprite", layer_name)),
+        buggy: image("spritebuggy3.aseprite", "Layer 1"),
+        map: image("spritemap5.aseprite", "Layer 1"),
+        wheels: art.animation("spritebuggy3.aseprite", &["Layer 1"], Some("drive")),
+        drill: art.animation("spriteharvester1.aseprite", &["Layer 1"], Some("drill")),
+        lamps: [
+            art.animation("spritecenter1.aseprite", &["red"], Some("blink")),
+            art.animation("spritecenter1.aseprite", &["yellow"], Some("light"))
+                .once(),
+            art.animation("spritecenter1.aseprite", &["green"], Some("light"))
+                .once(),
+        ],
+    };
+
+    let panel = |layer_name| image("spritepanel8.aseprite", layer_name);
+    let panel_assets = PanelAssetHandlers {
+        main: panel("main"),
+        center_icon: ["green", "yellow", "red"]
+            .map(|layer_name| image("iconcenter3.aseprite", layer_name)),
+        center_icon_frames: ["green", "yellow", "red"]
+            .map(|layer_name| art.animation("iconcenter3.aseprite", &[layer_name], None)),
+        buggy_icon: image("iconbuggy1.aseprite", "Layer 1"),
+        harv_icon: image("iconharvest1.aseprite", "Layer 1"),
+        ship: image("iconship1.aseprite", "Layer 1"),
+        space: ["space1", "space2", "space3"].map(panel),
+        stars: art.animation(
+            "spritepanel8.aseprite",
+            &["space1", "space2", "space3"],
+            None,
         ),
-        map_button: ["green", "red"].map(|layer_name| {
-            img_handle_and_size_from_bytes(button_bytes, layer_name, &mut textures)
+        harv_slots: [1, 2, 3, 4, 5, 6].map(|slot| {
+            ["off", "green", "yellow", "red"].map(|state| {
+                let layer_name = format!("harv{slot}{state}");
+                image("spritepanel8.aseprite", &layer_name)
+            })
         }),
+        exit: panel("exitup"),
+        harvester_button: ["harvesterup", "harvesteroff", "harvestergreen"].map(panel),
+        tank_button: ["tankup", "tankoff", "tankgreen"].map(panel),
+        tanks: ["tank1", "tank2", "tank3", "tank4", "tank5"].map(panel),
+        state_marks: ["work", "full", "broken"]
+            .map(|layer_name| image("iconstate1.aseprite", layer_name)),
+        layout: PanelLayout {
+            exit: art.slice("spritepanel8.aseprite#exit"),
+            harvester: art.slice("spritepanel8.aseprite#harvester"),
+            tank: art.slice("spritepanel8.aseprite#tank"),
+            gauge: art.slice("spritepanel8.aseprite#gauge"),
+            map: art.slice("spritepanel8.aseprite#map"),
+            slots: [1, 2, 3, 4, 5, 6]
+                .map(|slot| art.slice(&format!("spritepanel8.aseprite#slot{slot}"))),
+        },
+    };
+
+    let endings = EndingAssetHandlers {
+        win: image("spriteendgood.aseprite", "Layer 1"),
+        lose: image("spriteendbad.aseprite", "Layer 1"),
+    };
+    (terrain, panel_assets, endings)
+}
+
+pub fn load_sounds(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let mut add = |tones: &[Tone]| {
+        sources.add(AudioSource {
+            sound: render(tones),
+        })
+    };
+
+    commands.insert_resource(SoundAssetHandlers {
+        // Whole number of periods so the loop doesn't click
+        engine: add(&[
+            Tone::new(Wave::Saw, 55.0, 0.4).volume(0.3).held(),
+            Tone::new(Wave::Square, 110.0, 0.4).volume(0.1).held(),
+        ]),
+        brake: add(&[
+            Tone::new(Wave::Noise, 0.0, 0.4).volume(0.2),
+            Tone::new(Wave::Saw, 1800.0, 0.4)
+                .slide_to(1400.0)
+                .volume(0.1),
+        ]),
+        collision: add(&[
+            Tone::new(Wave::Sine, 90.0, 0.25).slide_to(35.0).volume(0.8),
+            Tone::new(Wave::Noise, 0.0, 0.08).volume(0.4),
+        ]),
+        pickup: add(&[
+            Tone::new(Wave::Square, 660.0, 0.08).volume(0.2),
+            Tone::new(Wave::Square, 990.0, 0.1).at(0.06).volume(0.2),
+        ]),
+        unload: add(&[
+            Tone::new(Wave::Square, 880.0, 0.1).volume(0.2),
+            Tone::new(Wave::Square, 660.0, 0.1).at(0.1).volume(0.2),
+            Tone::new(Wave::Square, 440.0, 0.2).at(0.2).volume(0.2),
+            Tone::new(Wave::Noise, 0.0, 0.4).volume(0.05),
+        ]),
+        repair: add(&[
+            Tone::new(Wave::Noise, 0.0, 0.05).volume(0.3),
+            Tone::new(Wave::Noise, 0.0, 0.05).at(0.12).volume(0.3),
+            Tone::new(Wave::Sine, 1320.0, 0.3).at(0.24).volume(0.4),
+        ]),
+        alarm: add(&[0.0, 0.4]
+            .into_iter()
+            .flat_map(|start| {
+                [
+                    Tone::new(Wave::Square, 880.0, 0.2).at(start).volume(0.15),
+                    Tone::new(Wave::Square, 660.0, 0.2)
+                        .at(start + 0.2)
+                        .volume(0.15),
+                ]
+            })
+            .collect::<Vec<_>>()),
+        canister: add(&[523.0, 659.0, 784.0, 1047.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, hz)| {
+                Tone::new(Wave::Sine, hz, 0.7)
+                    .at(i as f32 * 0.05)
+                    .volume(0.25)
+            })
+            .collect::<Vec<_>>()),
+        click: add(&[Tone::new(Wave::Square, 1000.0, 0.02).volume(0.15)]),
+        center_hum: add(&[
+            Tone::new(Wave::Saw, 80.0, 1.0).volume(0.15).held(),
+            Tone::new(Wave::Sine, 160.0, 1.0).volume(0.1).held(),
+        ]),
+        center_full: add(&[
+            Tone::new(Wave::Square, 1200.0, 0.1).volume(0.15),
+            Tone::new(Wave::Square, 1200.0, 0.1).at(0.2).volume(0.15),
+            Tone::silence(1.2),
+        ]),
+        center_alarm: add(&[
+            Tone::new(Wave::Square, 900.0, 0.3)
+                .slide_to(600.0)
+                .volume(0.2),
+            Tone::new(Wave::Square, 900.0, 0.3)
+                .slide_to(600.0)
+                .at(0.4)
+                .volume(0.2),
+            Tone::silence(0.8),
+        ]),
     });
+}
 
-    let center_icon_bytes = include_bytes!("../assets/iconcenter3.aseprite");
-    let panel_bytes = include_bytes!("../assets/spritepanel8.aseprite");
+pub fn load_music(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let theme = StaticSoundData::from_cursor(
+        Cursor::new(include_bytes!("../assets/theme.ogg")),
+        StaticSoundSettings::default(),
+    )
+    .unwrap();
+    let mut add = |tones: &[Tone]| {
+        sources.add(AudioSource {
+            sound: render(tones),
+        })
+    };
+    let arpeggio = |notes: &[f32], step: f32, secs: f32| {
+        notes
+            .iter()
+            .enumerate()
+            .map(|(i, hz)| {
+                Tone::new(Wave::Sine, *hz, secs)
+                    .at(i as f32 * step)
+                    .volume(0.3)
+            })
+            .collect::<Vec<_>>()
+    };
 
-    commands.insert_resource(PanelAssetHandlers {
-        center_icon: ["green", "yellow", "red"].map(|layer_name| {
-            let img = image_from_aseprite_layer_name_frame(center_icon_bytes, layer_name, 0);
-            let size = img.size();
-            (textures.add(img), size * PIXEL_MULTIPLIER)
-        }),
-        buggy_icon: img_handle_and_size_from_bytes(
-            include_bytes!("../assets/iconbuggy1.aseprite"),
-            "Layer 1",
-            &mut textures,
-        ),
-        harv_icon: img_handle_and_size_from_bytes(
-            include_bytes!("../assets/iconharvest1.aseprite"),
-            "Layer 1",
-            &mut textures,
-        ),
-        ship: img_handle_and_size_from_bytes(
-            include_bytes!("../assets/iconship1.aseprite"),
-            "Layer 1",
-            &mut textures,
-        ),
-        space: ["space1", "space2", "space3"].map(|layer_name| {
-            let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-            let size = img.size();
-            (textures.add(img), size * PIXEL_MULTIPLIER)
-        }),
-        harv_slots: [
-            ["harv1off", "harv1green", "harv1yellow", "harv1red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-            ["harv2off", "harv2green", "harv2yellow", "harv2red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-            ["harv3off", "harv3green", "harv3yellow", "harv3red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-            ["harv4off", "harv4green", "harv4yellow", "harv4red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-            ["harv5off", "harv5green", "harv5yellow", "harv5red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-            ["harv6off", "harv6green", "harv6yellow", "harv6red"].map(|layer_name| {
-                let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-                let size = img.size();
-                (textures.add(img), size * PIXEL_MULTIPLIER)
-            }),
-        ],
-        exit: img_handle_and_size_from_bytes(panel_bytes, "exitup", &mut textures),
-        harvester_button: ["harvesterup", "harvesteroff", "harvestergreen"].map(|layer_name| {
-            let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-            let size = img.size();
-            (textures.add(img), size * PIXEL_MULTIPLIER)
-        }),
-        tank_button: ["tankup", "tankoff", "tankgreen"].map(|layer_name| {
-            let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-            let size = img.size();
-            (textures.add(img), size * PIXEL_MULTIPLIER)
-        }),
-        helium_level: img_handle_and_size_from_bytes(panel_bytes, "he3", &mut textures),
-        tanks: ["tank1", "tank2", "tank3", "tank4", "tank5"].map(|layer_name| {
-            let img = image_from_aseprite_layer_name_frame(panel_bytes, layer_name, 0);
-            let size = img.size();
-            (textures.add(img), size * PIXEL_MULTIPLIER)
-        }),
+    // A slow tune over a low drone, quieter than the run
+    let mut title = arpeggio(&[440.0, 523.0, 659.0, 587.0, 523.0, 494.0], 1.0, 1.5);
+    title.extend([
+        Tone::new(Wave::Sine, 55.0, 8.0).volume(0.3).held(),
+        Tone::new(Wave::Sine, 110.0, 8.0).volume(0.1).held(),
+    ]);
+
+    commands.insert_resource(MusicAssetHandlers {
+        // Held tones in loops fit a whole number of periods in the loop
+        title: add(&title),
+        pressure: add(&[
+            Tone::new(Wave::Sine, 55.0, 2.0).volume(0.5).held(),
+            Tone::new(Wave::Noise, 0.0, 0.03).volume(0.4),
+            Tone::new(Wave::Noise, 0.0, 0.03).at(0.5).volume(0.2),
+            Tone::new(Wave::Noise, 0.0, 0.03).at(1.0).volume(0.4),
+            Tone::new(Wave::Noise, 0.0, 0.03).at(1.5).volume(0.2),
+        ]),
+        // Two tones a few hertz apart beat against each other
+        trouble: add(&[
+            Tone::new(Wave::Sine, 440.0, 4.0).volume(0.15).held(),
+            Tone::new(Wave::Sine, 444.0, 4.0).volume(0.15).held(),
+            Tone::new(Wave::Saw, 233.0, 4.0).volume(0.05).held(),
+        ]),
+        win: add(&arpeggio(&[523.0, 659.0, 784.0, 1047.0, 1319.0], 0.15, 2.0)),
+        lose: add(&arpeggio(&[440.0, 392.0, 349.0, 330.0, 220.0], 0.4, 2.0)),
+        outro: add(&arpeggio(
+            &[220.0, 262.0, 330.0, 440.0, 330.0, 262.0, 220.0, 165.0],
+            1.0,
+            1.0,
+        )),
+        theme: sources.add(AudioSource { sound: theme }),
     });
 }